@@ -70,7 +70,7 @@
 (print (simple-interest 1000 5 2)) ; 100
 
 (print "Compound interest (P=1000, R=5%, T=2 years):")
-(print (compound-interest 1000 5 2)) ; 1102.5
+(print (compound-interest 1000 5 2)) ; 2205/2, exactly 1102.5
 
 ; Area calculations
 (defn circle-area (radius)
@@ -104,4 +104,4 @@
 (print (celsius-to-fahrenheit 25))     ; 77
 (print (fahrenheit-to-celsius 77))     ; 25
 (print (miles-to-kilometers 10))       ; 16.0934
-(print (kilometers-to-miles 16.0934))  ; 10
\ No newline at end of file
+(print (kilometers-to-miles 16.0934))  ; 10.0
\ No newline at end of file
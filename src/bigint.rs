@@ -0,0 +1,387 @@
+//! Arbitrary-precision integers, used by the numeric tower once a result no
+//! longer fits in an `i64`.
+
+use std::cmp::Ordering;
+use std::ops::{Add, Mul, Neg, Sub};
+use std::str::FromStr;
+
+/// Each digit holds nine decimal digits, which keeps printing and parsing
+/// simple and lets two digits multiply without overflowing a `u64`.
+const BASE: u64 = 1_000_000_000;
+const BASE_DIGITS: usize = 9;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    /// Base 10^9 digits, least significant first, with no trailing zeros.
+    /// Zero has no digits and is never negative.
+    digits: Vec<u32>,
+}
+
+impl BigInt {
+    fn from_parts(negative: bool, mut digits: Vec<u32>) -> Self {
+        trim(&mut digits);
+        Self {
+            negative: negative && !digits.is_empty(),
+            digits,
+        }
+    }
+    
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+    
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+    
+    pub fn abs(&self) -> Self {
+        Self::from_parts(false, self.digits.clone())
+    }
+    
+    /// Returns the value as an `i64`, if it fits.
+    pub fn to_i64(&self) -> Option<i64> {
+        // Three digits already exceed the range of an i64
+        if self.digits.len() > 3 {
+            return None;
+        }
+        
+        let magnitude = self.digits.iter().rev().fold(0i128, |acc, &d| acc * BASE as i128 + d as i128);
+        i64::try_from(if self.negative { -magnitude } else { magnitude }).ok()
+    }
+    
+    /// Returns the nearest `f64`, or an infinity if the value is out of range.
+    pub fn to_f64(&self) -> f64 {
+        let magnitude = self.digits.iter().rev().fold(0.0, |acc, &d| acc * BASE as f64 + d as f64);
+        if self.negative { -magnitude } else { magnitude }
+    }
+    
+    /// Divides, rounding the quotient towards zero; the remainder takes the
+    /// sign of `self`. Panics if `divisor` is zero.
+    pub fn div_rem(&self, divisor: &BigInt) -> (BigInt, BigInt) {
+        assert!(!divisor.is_zero(), "BigInt division by zero");
+        
+        let (quotient, remainder) = div_rem_magnitude(&self.digits, &divisor.digits);
+        (
+            Self::from_parts(self.negative != divisor.negative, quotient),
+            Self::from_parts(self.negative, remainder),
+        )
+    }
+    
+    /// The greatest common divisor of `self` and `other`, which is never
+    /// negative.
+    pub fn gcd(&self, other: &BigInt) -> BigInt {
+        let mut a = self.abs();
+        let mut b = other.abs();
+        while !b.is_zero() {
+            let (_, remainder) = a.div_rem(&b);
+            a = b;
+            b = remainder;
+        }
+        a
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(n: i64) -> Self {
+        let mut magnitude = n.unsigned_abs();
+        let mut digits = Vec::new();
+        while magnitude > 0 {
+            digits.push((magnitude % BASE) as u32);
+            magnitude /= BASE;
+        }
+        Self::from_parts(n < 0, digits)
+    }
+}
+
+/// Parses an optionally signed run of decimal digits.
+impl FromStr for BigInt {
+    type Err = ();
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (negative, digits) = match s.as_bytes().first() {
+            Some(b'-') => (true, &s[1..]),
+            Some(b'+') => (false, &s[1..]),
+            _ => (false, s),
+        };
+        
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(());
+        }
+        
+        // Split into chunks of nine digits, starting from the right
+        let mut limbs = Vec::new();
+        let mut end = digits.len();
+        while end > 0 {
+            let start = end.saturating_sub(BASE_DIGITS);
+            limbs.push(digits[start..end].parse().map_err(|_| ())?);
+            end = start;
+        }
+        
+        Ok(Self::from_parts(negative, limbs))
+    }
+}
+
+impl std::fmt::Display for BigInt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Some((most, rest)) = self.digits.split_last() else {
+            return write!(f, "0");
+        };
+        
+        if self.negative {
+            write!(f, "-")?;
+        }
+        write!(f, "{}", most)?;
+        for digit in rest.iter().rev() {
+            write!(f, "{:09}", digit)?;
+        }
+        Ok(())
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_magnitude(&self.digits, &other.digits),
+            (true, true) => cmp_magnitude(&other.digits, &self.digits),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Neg for &BigInt {
+    type Output = BigInt;
+    
+    fn neg(self) -> BigInt {
+        BigInt::from_parts(!self.negative, self.digits.clone())
+    }
+}
+
+impl Add for &BigInt {
+    type Output = BigInt;
+    
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::from_parts(self.negative, add_magnitude(&self.digits, &other.digits));
+        }
+        
+        // Opposite signs: subtract the smaller magnitude from the larger
+        match cmp_magnitude(&self.digits, &other.digits) {
+            Ordering::Less => BigInt::from_parts(other.negative, sub_magnitude(&other.digits, &self.digits)),
+            _ => BigInt::from_parts(self.negative, sub_magnitude(&self.digits, &other.digits)),
+        }
+    }
+}
+
+impl Sub for &BigInt {
+    type Output = BigInt;
+    
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &-other
+    }
+}
+
+impl Mul for &BigInt {
+    type Output = BigInt;
+    
+    fn mul(self, other: &BigInt) -> BigInt {
+        BigInt::from_parts(self.negative != other.negative, mul_magnitude(&self.digits, &other.digits))
+    }
+}
+
+fn trim(digits: &mut Vec<u32>) {
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+}
+
+fn cmp_magnitude(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0;
+    for i in 0..a.len().max(b.len()) {
+        let sum = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        result.push((sum % BASE) as u32);
+        carry = sum / BASE;
+    }
+    if carry > 0 {
+        result.push(carry as u32);
+    }
+    result
+}
+
+/// Computes `a - b`, where `a` is at least `b`.
+fn sub_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0;
+    for (i, &digit) in a.iter().enumerate() {
+        let subtrahend = *b.get(i).unwrap_or(&0) as i64 + borrow;
+        let mut difference = digit as i64 - subtrahend;
+        borrow = 0;
+        if difference < 0 {
+            difference += BASE as i64;
+            borrow = 1;
+        }
+        result.push(difference as u32);
+    }
+    trim(&mut result);
+    result
+}
+
+fn mul_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = vec![0u64; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0;
+        for (j, &y) in b.iter().enumerate() {
+            let product = result[i + j] + x as u64 * y as u64 + carry;
+            result[i + j] = product % BASE;
+            carry = product / BASE;
+        }
+        result[i + b.len()] += carry;
+    }
+    
+    let mut result: Vec<u32> = result.into_iter().map(|digit| digit as u32).collect();
+    trim(&mut result);
+    result
+}
+
+/// Divides by a single digit.
+fn div_rem_digit(a: &[u32], divisor: u32) -> (Vec<u32>, Vec<u32>) {
+    let mut quotient = vec![0; a.len()];
+    let mut remainder = 0;
+    for i in (0..a.len()).rev() {
+        let current = remainder * BASE + a[i] as u64;
+        quotient[i] = (current / divisor as u64) as u32;
+        remainder = current % divisor as u64;
+    }
+    
+    trim(&mut quotient);
+    let mut remainder = vec![remainder as u32];
+    trim(&mut remainder);
+    (quotient, remainder)
+}
+
+/// The value of `digits` with the lowest `skip` digits dropped. Only used
+/// where that leaves few enough digits to fit.
+fn leading_value(digits: &[u32], skip: usize) -> u128 {
+    digits.iter().skip(skip).rev().fold(0, |acc, &d| acc * BASE as u128 + d as u128)
+}
+
+/// Schoolbook long division. Comparing the leading digits of the remainder
+/// and divisor pins each quotient digit down to a narrow range, and a binary
+/// search over that range settles it.
+fn div_rem_magnitude(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if b.len() == 1 {
+        return div_rem_digit(a, b[0]);
+    }
+    
+    // Below the divisor's top two digits, digits only nudge the estimate
+    let skip = b.len() - 2;
+    let divisor_top = leading_value(b, skip);
+    
+    let mut quotient = vec![0; a.len()];
+    let mut remainder: Vec<u32> = Vec::new();
+    
+    for i in (0..a.len()).rev() {
+        // Bring down the next digit
+        remainder.insert(0, a[i]);
+        trim(&mut remainder);
+        
+        let remainder_top = leading_value(&remainder, skip);
+        let max_digit = BASE as u128 - 1;
+        let mut low = (remainder_top / (divisor_top + 1)).min(max_digit) as u32;
+        let mut high = ((remainder_top + 1) / divisor_top).min(max_digit) as u32;
+        
+        while low < high {
+            let mid = low + (high - low).div_ceil(2);
+            if cmp_magnitude(&mul_magnitude(b, &[mid]), &remainder) == Ordering::Greater {
+                high = mid - 1;
+            } else {
+                low = mid;
+            }
+        }
+        
+        if low > 0 {
+            remainder = sub_magnitude(&remainder, &mul_magnitude(b, &[low]));
+        }
+        quotient[i] = low;
+    }
+    
+    trim(&mut quotient);
+    (quotient, remainder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn big(s: &str) -> BigInt {
+        s.parse().unwrap()
+    }
+    
+    /// Checks `div_rem` against `i128` arithmetic, which also rounds towards
+    /// zero and gives the remainder the dividend's sign.
+    fn check_div_rem(a: i128, b: i128) {
+        let (quotient, remainder) = big(&a.to_string()).div_rem(&big(&b.to_string()));
+        assert_eq!(quotient.to_string(), (a / b).to_string(), "{} / {}", a, b);
+        assert_eq!(remainder.to_string(), (a % b).to_string(), "{} % {}", a, b);
+    }
+    
+    #[test]
+    fn div_rem_with_negative_dividend() {
+        check_div_rem(-7, 2);
+        check_div_rem(-1_000_000_000_000_000_000_007, 1000);
+        check_div_rem(-1_000_000_000_000_000_000_007, -1000);
+        check_div_rem(-999_999_999, 1_000_000_000);
+    }
+    
+    #[test]
+    fn div_rem_with_divisor_over_one_limb() {
+        check_div_rem(123_456_789_012_345_678_901_234_567_890, 9_876_543_210_987);
+        check_div_rem(-123_456_789_012_345_678_901_234_567_890, 9_876_543_210_987);
+        check_div_rem(123_456_789_012_345_678_901_234_567_890, -1_000_000_001);
+        check_div_rem(1_000_000_000_000_000_000_000_000_000, 999_999_999_999_999_999);
+        check_div_rem(999_999_999_999_999_999, 1_000_000_000_000_000_000);
+        check_div_rem(999_999_999_999_999_999_999_999_999, 1_000_000_001);
+    }
+    
+    #[test]
+    fn addition_carries_across_limbs() {
+        assert_eq!((&big("999999999999999999") + &big("1")).to_string(), "1000000000000000000");
+        assert_eq!((&big("1000000000000000000") - &big("1")).to_string(), "999999999999999999");
+        assert_eq!((&big("-1") + &big("1000000000000000000")).to_string(), "999999999999999999");
+    }
+    
+    #[test]
+    fn multiplication_carries_across_limbs() {
+        let a: i128 = 999_999_999_999_999_999;
+        assert_eq!((&big(&a.to_string()) * &big(&a.to_string())).to_string(), (a * a).to_string());
+        assert_eq!((&big("-999999999") * &big("999999999")).to_string(), "-999999998000000001");
+        assert_eq!((&big("123456789") * &big("0")).to_string(), "0");
+    }
+    
+    #[test]
+    fn gcd_ignores_signs() {
+        assert_eq!(big("-12").gcd(&big("18")).to_string(), "6");
+        assert_eq!(big("0").gcd(&big("-5")).to_string(), "5");
+        assert_eq!(big("1000000000000000000").gcd(&big("999999999")).to_string(), "1");
+    }
+    
+    #[test]
+    fn i64_extremes_convert_both_ways() {
+        assert_eq!(BigInt::from(i64::MIN).to_string(), i64::MIN.to_string());
+        assert_eq!(BigInt::from(i64::MIN).to_i64(), Some(i64::MIN));
+        assert_eq!((-&BigInt::from(i64::MIN)).to_i64(), None);
+        assert_eq!(BigInt::from(i64::MAX).to_i64(), Some(i64::MAX));
+    }
+}
@@ -0,0 +1,155 @@
+//! Hash maps and sets of values. Both remember the order their entries were
+//! added in, so they print the same way every time, but compare and hash
+//! without regard to it.
+
+use crate::evaluator::Value;
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+#[derive(Debug, Clone, Default)]
+pub struct Map {
+    entries: Vec<(Value, Value)>,
+    /// The position of each key in `entries`
+    index: HashMap<Value, usize>,
+}
+
+impl Map {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    
+    pub fn get(&self, key: &Value) -> Option<&Value> {
+        self.index.get(key).map(|&i| &self.entries[i].1)
+    }
+    
+    pub fn contains_key(&self, key: &Value) -> bool {
+        self.index.contains_key(key)
+    }
+    
+    /// Adds or replaces the entry for `key`. A replaced entry keeps its
+    /// original position.
+    pub fn insert(&mut self, key: Value, value: Value) {
+        match self.index.get(&key) {
+            Some(&i) => self.entries[i].1 = value,
+            None => {
+                self.index.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+    
+    pub fn remove(&mut self, key: &Value) -> Option<Value> {
+        let i = self.index.remove(key)?;
+        let (_, value) = self.entries.remove(i);
+        
+        // Everything after the removed entry moved down one place
+        for (position, (key, _)) in self.entries.iter().enumerate().skip(i) {
+            self.index.insert(key.clone(), position);
+        }
+        Some(value)
+    }
+    
+    pub fn iter(&self) -> impl Iterator<Item = (&Value, &Value)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+    
+    pub fn keys(&self) -> impl Iterator<Item = &Value> {
+        self.entries.iter().map(|(key, _)| key)
+    }
+    
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.entries.iter().map(|(_, value)| value)
+    }
+}
+
+impl FromIterator<(Value, Value)> for Map {
+    fn from_iter<I: IntoIterator<Item = (Value, Value)>>(iter: I) -> Self {
+        let mut map = Map::new();
+        for (key, value) in iter {
+            map.insert(key, value);
+        }
+        map
+    }
+}
+
+impl PartialEq for Map {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl Hash for Map {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len().hash(state);
+        unordered_hash(self.iter()).hash(state);
+    }
+}
+
+/// A set is a map whose keys are its elements.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Set {
+    map: Map,
+}
+
+impl Set {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+    
+    pub fn contains(&self, value: &Value) -> bool {
+        self.map.contains_key(value)
+    }
+    
+    pub fn insert(&mut self, value: Value) {
+        self.map.insert(value, Value::Nil);
+    }
+    
+    pub fn remove(&mut self, value: &Value) -> bool {
+        self.map.remove(value).is_some()
+    }
+    
+    pub fn iter(&self) -> impl Iterator<Item = &Value> {
+        self.map.keys()
+    }
+}
+
+impl FromIterator<Value> for Set {
+    fn from_iter<I: IntoIterator<Item = Value>>(iter: I) -> Self {
+        Self {
+            map: iter.into_iter().map(|value| (value, Value::Nil)).collect(),
+        }
+    }
+}
+
+impl Hash for Set {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len().hash(state);
+        unordered_hash(self.iter()).hash(state);
+    }
+}
+
+/// Combines the hashes of `items` so that their order doesn't matter.
+fn unordered_hash<T: Hash>(items: impl Iterator<Item = T>) -> u64 {
+    items.fold(0u64, |acc, item| {
+        let mut hasher = DefaultHasher::new();
+        item.hash(&mut hasher);
+        acc.wrapping_add(hasher.finish())
+    })
+}
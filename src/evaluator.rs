@@ -1,19 +1,59 @@
-use crate::parser::Expr;
+use crate::collections::{Map, Set};
+use crate::keyword::Keyword;
+use crate::module::{self, Modules};
+use crate::number::Number;
+use crate::parser::{self, Expr, ExprKind};
+use crate::span::Span;
+use crate::tokenizer::{self, char_literal, escape_string};
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::rc::Rc;
+
+/// Names handled directly by the evaluator rather than bound in the
+/// environment.
+pub const SPECIAL_FORMS: &[&str] = &[
+    "quote", "def", "set!", "defn", "defmacro", "lambda", "do", "if", "cond", "and", "or", "let", "let*", "letrec", "try",
+    "require", "provide",
+];
 
 #[derive(Debug, Clone)]
 pub enum Value {
-    Number(f64),
+    Number(Number),
     String(String),
+    Char(char),
     Symbol(String),
+    Keyword(Keyword),
     Boolean(bool),
     List(Vec<Value>),
+    Vector(Vec<Value>),
+    Map(Box<Map>),
+    Set(Box<Set>),
     Function(fn(&[Value]) -> Result<Value, EvalError>),
+    // Builtins that need to see the environment they are called from
+    EnvFunction(fn(&[Value], &Environment) -> Result<Value, EvalError>),
+    // Parameters and names are shared rather than stored inline, like the
+    // maps and sets above, keeping `Value` small: every value on the Rust
+    // stack is as big as the largest
     Lambda {
-        params: Vec<String>,
-        body: Vec<Expr>,
+        name: Option<Rc<str>>,
+        params: Rc<Params>,
+        body: Rc<[Expr]>,
+        closure: Environment,
+    },
+    Macro {
+        params: Rc<Params>,
+        body: Rc<[Expr]>,
         closure: Environment,
     },
+    /// A mutable cell, shared by every copy of the value
+    Box(Rc<RefCell<Value>>),
+    /// A caught builtin error, as seen by a `catch` handler
+    Error(Box<ErrorKind>),
+    /// An environment, for `eval` to evaluate code in
+    Environment(Environment),
     Nil,
 }
 
@@ -22,34 +62,349 @@ impl PartialEq for Value {
         match (self, other) {
             (Value::Number(a), Value::Number(b)) => a == b,
             (Value::String(a), Value::String(b)) => a == b,
+            (Value::Char(a), Value::Char(b)) => a == b,
             (Value::Symbol(a), Value::Symbol(b)) => a == b,
+            (Value::Keyword(a), Value::Keyword(b)) => a == b,
             (Value::Boolean(a), Value::Boolean(b)) => a == b,
             (Value::List(a), Value::List(b)) => a == b,
+            (Value::Vector(a), Value::Vector(b)) => a == b,
+            (Value::Map(a), Value::Map(b)) => a == b,
+            (Value::Set(a), Value::Set(b)) => a == b,
             (Value::Nil, Value::Nil) => true,
-            _ => false, // Functions and lambdas are not comparable
+            // Boxes are the same only if they are the same cell
+            (Value::Box(a), Value::Box(b)) => Rc::ptr_eq(a, b),
+            (Value::Environment(a), Value::Environment(b)) => Rc::ptr_eq(&a.frame, &b.frame),
+            _ => false, // Functions, lambdas and macros are not comparable
         }
     }
 }
 
-#[derive(Debug)]
-pub enum EvalError {
+// Values that are never equal, like functions or NaN, make poor map keys
+// since they can't be looked up again, but they are still allowed.
+impl Eq for Value {}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Value::Number(n) => n.hash(state),
+            Value::String(s) | Value::Symbol(s) => s.hash(state),
+            Value::Keyword(k) => k.hash(state),
+            Value::Char(ch) => ch.hash(state),
+            Value::Boolean(b) => b.hash(state),
+            Value::List(elements) | Value::Vector(elements) => elements.hash(state),
+            Value::Map(map) => map.hash(state),
+            Value::Set(set) => set.hash(state),
+            Value::Box(cell) => Rc::as_ptr(cell).hash(state),
+            Value::Environment(env) => Rc::as_ptr(&env.frame).hash(state),
+            // Everything else is only equal to itself, if that
+            _ => {}
+        }
+    }
+}
+
+impl Value {
+    /// The name of the value's type, as shown to users.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Char(_) => "char",
+            Value::Symbol(_) => "symbol",
+            Value::Keyword(_) => "keyword",
+            Value::Boolean(_) => "boolean",
+            Value::List(_) => "list",
+            Value::Vector(_) => "vector",
+            Value::Map(_) => "map",
+            Value::Set(_) => "set",
+            Value::Function(_) | Value::EnvFunction(_) | Value::Lambda { .. } => "function",
+            Value::Macro { .. } => "macro",
+            Value::Box(_) => "box",
+            Value::Error(_) => "error",
+            Value::Environment(_) => "environment",
+            Value::Nil => "nil",
+        }
+    }
+    
+    /// Converts code to the data it reads as, which is what quoting it gives.
+    /// `true` and `false` read as booleans and `nil` as nil; every other
+    /// symbol stays a symbol.
+    pub fn from_expr(expr: &Expr) -> Value {
+        match &expr.kind {
+            ExprKind::Number(n) => Value::Number(n.clone()),
+            ExprKind::String(s) => Value::String(s.clone()),
+            ExprKind::Keyword(k) => Value::Keyword(k.clone()),
+            ExprKind::Char(ch) => Value::Char(*ch),
+            ExprKind::Symbol(s) => match s.as_str() {
+                "true" => Value::Boolean(true),
+                "false" => Value::Boolean(false),
+                "nil" => Value::Nil,
+                _ => Value::Symbol(s.clone()),
+            },
+            ExprKind::List(elements) => Value::List(elements.iter().map(Value::from_expr).collect()),
+            ExprKind::Vector(elements) => Value::Vector(elements.iter().map(Value::from_expr).collect()),
+            ExprKind::Map(elements) => Value::Map(Box::new(
+                elements
+                    .chunks(2)
+                    .map(|pair| (Value::from_expr(&pair[0]), Value::from_expr(&pair[1])))
+                    .collect(),
+            )),
+            ExprKind::Set(elements) => Value::Set(Box::new(elements.iter().map(Value::from_expr).collect())),
+            // Nested quotation forms are kept as data, e.g. ''a => (quote a)
+            ExprKind::Quote(expr) => quoted_form("quote", Value::from_expr(expr)),
+            ExprKind::Quasiquote(expr) => quoted_form("quasiquote", Value::from_expr(expr)),
+            ExprKind::Unquote(expr) => quoted_form("unquote", Value::from_expr(expr)),
+            ExprKind::UnquoteSplicing(expr) => quoted_form("unquote-splicing", Value::from_expr(expr)),
+        }
+    }
+    
+    /// Converts data back into the code that reads as it, the inverse of
+    /// `from_expr`. Fails for values with no written form, such as functions
+    /// and boxes. The code has no place in any source, so it is given an
+    /// empty span.
+    pub fn to_expr(&self) -> Result<Expr, EvalError> {
+        self.to_expr_at(Span::default())
+    }
+    
+    /// Like `to_expr`, but gives every expression `span`. Macro expansions
+    /// use the location of the macro call.
+    pub fn to_expr_at(&self, span: Span) -> Result<Expr, EvalError> {
+        let kind = match self {
+            Value::Number(n) => ExprKind::Number(n.clone()),
+            Value::String(s) => ExprKind::String(s.clone()),
+            Value::Symbol(s) => ExprKind::Symbol(s.clone()),
+            Value::Keyword(k) => ExprKind::Keyword(k.clone()),
+            Value::Char(ch) => ExprKind::Char(*ch),
+            Value::Boolean(b) => ExprKind::Symbol(b.to_string()),
+            Value::Nil => ExprKind::Symbol("nil".to_string()),
+            Value::List(elements) => {
+                // Turn (quote x) and friends back into their reader forms
+                if let [Value::Symbol(name), inner] = elements.as_slice() {
+                    let wrap: Option<fn(Box<Expr>) -> ExprKind> = match name.as_str() {
+                        "quote" => Some(ExprKind::Quote),
+                        "quasiquote" => Some(ExprKind::Quasiquote),
+                        "unquote" => Some(ExprKind::Unquote),
+                        "unquote-splicing" => Some(ExprKind::UnquoteSplicing),
+                        _ => None,
+                    };
+                    if let Some(wrap) = wrap {
+                        return Ok(Expr::new(wrap(Box::new(inner.to_expr_at(span)?)), span));
+                    }
+                }
+                
+                ExprKind::List(to_exprs(elements.iter(), span)?)
+            }
+            Value::Vector(elements) => ExprKind::Vector(to_exprs(elements.iter(), span)?),
+            Value::Map(map) => ExprKind::Map(to_exprs(map.iter().flat_map(|(key, value)| [key, value]), span)?),
+            Value::Set(set) => ExprKind::Set(to_exprs(set.iter(), span)?),
+            _ => return Err(ErrorKind::TypeError(format!("cannot convert {} to code", self)).into()),
+        };
+        Ok(Expr::new(kind, span))
+    }
+}
+
+fn to_exprs<'a>(values: impl Iterator<Item = &'a Value>, span: Span) -> Result<Rc<[Expr]>, EvalError> {
+    values.map(|value| value.to_expr_at(span)).collect()
+}
+
+/// A parsed parameter list: `(a b &optional c (d default) &rest more)`, or
+/// the dotted shorthand `(a b . more)` for a rest parameter.
+#[derive(Debug, Clone, Default)]
+pub struct Params {
+    pub required: Vec<String>,
+    pub optional: Vec<(String, Option<Expr>)>,
+    pub rest: Option<String>,
+}
+
+impl Params {
+    fn accepts(&self, count: usize) -> bool {
+        count >= self.required.len()
+            && (self.rest.is_some() || count <= self.required.len() + self.optional.len())
+    }
+    
+    fn describe_arity(&self) -> String {
+        let min = self.required.len();
+        let max = min + self.optional.len();
+        let plural = |n: usize| if n == 1 { "argument" } else { "arguments" };
+        
+        if self.rest.is_some() {
+            format!("at least {} {}", min, plural(min))
+        } else if min == max {
+            format!("{} {}", min, plural(min))
+        } else {
+            format!("{} to {} {}", min, max, plural(max))
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ErrorKind {
     UndefinedSymbol(String),
     TypeError(String),
     ArityError(String),
     DivisionByZero,
     InvalidFunction(String),
+    /// An index outside the bounds of a list or vector
+    IndexError(String),
+    /// A module or file that couldn't be found or loaded
+    ModuleError(String),
+    /// Text given to `read` or `read-string` that isn't valid syntax
+    ReadError(String),
+    /// Calls nested more deeply than `MAX_DEPTH`
+    RecursionError,
+    /// A value raised with `throw`
+    Thrown(Box<Value>),
+}
+
+impl ErrorKind {
+    /// Every name `name` can return.
+    pub const NAMES: &'static [&'static str] = &[
+        "undefined-symbol",
+        "type-error",
+        "arity-error",
+        "division-by-zero",
+        "invalid-function",
+        "index-error",
+        "module-error",
+        "read-error",
+        "recursion-error",
+        "user",
+    ];
+    
+    /// The name `catch` clauses use to select errors of this kind.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorKind::UndefinedSymbol(_) => "undefined-symbol",
+            ErrorKind::TypeError(_) => "type-error",
+            ErrorKind::ArityError(_) => "arity-error",
+            ErrorKind::DivisionByZero => "division-by-zero",
+            ErrorKind::InvalidFunction(_) => "invalid-function",
+            ErrorKind::IndexError(_) => "index-error",
+            ErrorKind::ModuleError(_) => "module-error",
+            ErrorKind::ReadError(_) => "read-error",
+            ErrorKind::RecursionError => "recursion-error",
+            ErrorKind::Thrown(_) => "user",
+        }
+    }
+}
+
+/// An evaluation error, located at the innermost expression it arose from
+/// when that is known.
+#[derive(Debug)]
+pub struct EvalError {
+    pub kind: ErrorKind,
+    pub span: Option<Span>,
+    /// The procedure calls the error escaped from, innermost first.
+    pub backtrace: Vec<CallFrame>,
 }
 
+/// A procedure activation: the function's name and where it was called from.
 #[derive(Debug, Clone)]
+pub struct CallFrame {
+    pub function: Rc<str>,
+    pub call_site: Span,
+}
+
+impl EvalError {
+    /// Attaches `span` unless the error has already been located more precisely.
+    fn at(mut self, span: Span) -> Self {
+        self.span.get_or_insert(span);
+        self
+    }
+    
+    pub fn in_frame(mut self, call: CallFrame) -> Self {
+        self.backtrace.push(call);
+        self
+    }
+    
+    /// Locates frames pushed by `apply`, for calls made by a builtin, at
+    /// `span`, the call to that builtin. Files evaluated by `require` and
+    /// `load` push such frames too.
+    fn called_from(mut self, span: Span) -> Self {
+        for frame in &mut self.backtrace {
+            if frame.call_site == Span::default() {
+                frame.call_site = span;
+            }
+        }
+        self
+    }
+    
+    /// Formats the backtrace, innermost call first, listing at most
+    /// `max_depth` frames. Returns `None` if the error has no backtrace.
+    pub fn backtrace_report(&self, origin: &str, max_depth: usize) -> Option<String> {
+        if self.backtrace.is_empty() {
+            return None;
+        }
+        
+        let mut report = String::from("Backtrace (most recent call first):");
+        for (i, call) in self.backtrace.iter().take(max_depth).enumerate() {
+            // Calls made from code in some other source, such as a module,
+            // are located in that source
+            let location = if call.call_site == Span::default() {
+                "<unknown>".to_string()
+            } else {
+                let call_origin = call.call_site.source.map(|source| source.get().name.clone());
+                let call_origin = call_origin.as_deref().unwrap_or(origin);
+                format!("{}:{}:{}", call_origin, call.call_site.line, call.call_site.column)
+            };
+            report.push_str(&format!("\n  {}: {} called at {}", i, call.function, location));
+        }
+        if self.backtrace.len() > max_depth {
+            report.push_str(&format!("\n  ... {} more", self.backtrace.len() - max_depth));
+        }
+        Some(report)
+    }
+}
+
+impl From<ErrorKind> for EvalError {
+    fn from(kind: ErrorKind) -> Self {
+        Self {
+            kind,
+            span: None,
+            backtrace: Vec::new(),
+        }
+    }
+}
+
+/// A lexical environment: a handle to a frame of bindings plus a link to the
+/// enclosing frame. Cloning an `Environment` is cheap and shares the frame, so
+/// closures capture their defining scope by reference.
+#[derive(Clone)]
 pub struct Environment {
+    frame: Rc<RefCell<Frame>>,
+    /// Shared by every environment in the program, including the namespaces
+    /// of the modules it loads
+    modules: Rc<RefCell<Modules>>,
+}
+
+struct Frame {
     bindings: HashMap<String, Value>,
+    parent: Option<Environment>,
+    /// Whether this is the top level of a program or module, where `def`
+    /// binds however deeply it is nested
+    global: bool,
 }
 
 impl Environment {
     pub fn new() -> Self {
-        let mut env = Self {
-            bindings: HashMap::new(),
-        };
+        Self::with_builtins(Rc::new(RefCell::new(Modules::new())))
+    }
+    
+    /// Creates a fresh global environment, for a module to be evaluated in,
+    /// that shares this one's modules. Definitions go in a frame of their
+    /// own, above the builtins, so that they can be told apart.
+    pub fn new_namespace(&self) -> Self {
+        let namespace = Self::with_builtins(self.modules.clone()).extend();
+        namespace.frame.borrow_mut().global = true;
+        namespace
+    }
+    
+    pub fn modules(&self) -> Rc<RefCell<Modules>> {
+        self.modules.clone()
+    }
+    
+    fn with_builtins(modules: Rc<RefCell<Modules>>) -> Self {
+        let env = Self::with_parent(None, modules);
         
         // Add built-in functions
         env.define("+", Value::Function(builtin_add));
@@ -68,34 +423,186 @@ impl Environment {
         env.define("length", Value::Function(builtin_length));
         env.define("null?", Value::Function(builtin_null));
         env.define("reverse", Value::Function(builtin_reverse));
+        env.define("map", Value::EnvFunction(builtin_map));
+        env.define("for-each", Value::EnvFunction(builtin_for_each));
+        env.define("filter", Value::EnvFunction(builtin_filter));
+        env.define("reduce", Value::EnvFunction(builtin_reduce));
+        env.define("fold-left", Value::EnvFunction(builtin_fold_left));
+        env.define("fold-right", Value::EnvFunction(builtin_fold_right));
+        env.define("append", Value::Function(builtin_append));
+        env.define("apply", Value::EnvFunction(builtin_apply));
+        env.define("sort", Value::EnvFunction(builtin_sort));
+        env.define("member", Value::Function(builtin_member));
+        env.define("range", Value::Function(builtin_range));
+        env.define("take", Value::Function(builtin_take));
+        env.define("drop", Value::Function(builtin_drop));
+        env.define("zip", Value::Function(builtin_zip));
+        env.define("eq?", Value::Function(builtin_eq));
+        env.define("number?", Value::Function(builtin_is_number));
+        env.define("integer?", Value::Function(builtin_is_integer));
+        env.define("exact?", Value::Function(builtin_is_exact));
+        env.define("string?", Value::Function(builtin_is_string));
+        env.define("char?", Value::Function(builtin_is_char));
+        env.define("symbol?", Value::Function(builtin_is_symbol));
+        env.define("boolean?", Value::Function(builtin_is_boolean));
+        env.define("list?", Value::Function(builtin_is_list));
+        env.define("vector?", Value::Function(builtin_is_vector));
+        env.define("map?", Value::Function(builtin_is_map));
+        env.define("set?", Value::Function(builtin_is_set));
+        env.define("nil?", Value::Function(builtin_is_nil));
+        env.define("function?", Value::Function(builtin_is_function));
         env.define("print", Value::Function(builtin_print));
+        env.define("read", Value::Function(builtin_read));
+        env.define("read-string", Value::Function(builtin_read_string));
+        env.define("eval", Value::EnvFunction(builtin_eval));
+        env.define("load", Value::EnvFunction(builtin_load));
+        env.define("current-environment", Value::EnvFunction(builtin_current_environment));
+        env.define("make-environment", Value::EnvFunction(builtin_make_environment));
+        env.define("substring", Value::Function(builtin_substring));
+        env.define("string-append", Value::Function(builtin_string_append));
+        env.define("string-split", Value::Function(builtin_string_split));
+        env.define("string-join", Value::Function(builtin_string_join));
+        env.define("string-upcase", Value::Function(builtin_string_upcase));
+        env.define("string-downcase", Value::Function(builtin_string_downcase));
+        env.define("string-trim", Value::Function(builtin_string_trim));
+        env.define("string-index", Value::EnvFunction(builtin_string_index));
+        env.define("string->list", Value::Function(builtin_string_to_list));
+        env.define("list->string", Value::Function(builtin_list_to_string));
+        env.define("string->number", Value::Function(builtin_string_to_number));
+        env.define("number->string", Value::Function(builtin_number_to_string));
+        env.define("string-replace", Value::Function(builtin_string_replace));
         env.define("min", Value::Function(builtin_min));
         env.define("max", Value::Function(builtin_max));
         env.define("abs", Value::Function(builtin_abs));
         env.define("mod", Value::Function(builtin_mod));
         env.define("not", Value::Function(builtin_not));
-        env.define("and", Value::Function(builtin_and));
-        env.define("or", Value::Function(builtin_or));
+        env.define("macroexpand-1", Value::EnvFunction(builtin_macroexpand_1));
+        env.define("macroexpand", Value::EnvFunction(builtin_macroexpand));
+        env.define("box", Value::Function(builtin_box));
+        env.define("unbox", Value::Function(builtin_unbox));
+        env.define("set-box!", Value::Function(builtin_set_box));
+        env.define("box?", Value::Function(builtin_is_box));
+        env.define("get", Value::Function(builtin_get));
+        env.define("assoc", Value::Function(builtin_assoc));
+        env.define("dissoc", Value::Function(builtin_dissoc));
+        env.define("keys", Value::Function(builtin_keys));
+        env.define("vals", Value::Function(builtin_vals));
+        env.define("contains?", Value::Function(builtin_contains));
+        env.define("nth", Value::Function(builtin_nth));
+        env.define("conj", Value::Function(builtin_conj));
+        env.define("update", Value::EnvFunction(builtin_update));
+        env.define("keyword", Value::Function(builtin_keyword));
+        env.define("keyword?", Value::Function(builtin_is_keyword));
+        env.define("name", Value::Function(builtin_name));
+        env.define("throw", Value::Function(builtin_throw));
+        env.define("error?", Value::Function(builtin_is_error));
+        env.define("error-kind", Value::Function(builtin_error_kind));
+        env.define("error-message", Value::Function(builtin_error_message));
         
         env
     }
     
-    pub fn define(&mut self, name: &str, value: Value) {
-        self.bindings.insert(name.to_string(), value);
+    fn with_parent(parent: Option<Environment>, modules: Rc<RefCell<Modules>>) -> Self {
+        Self {
+            frame: Rc::new(RefCell::new(Frame {
+                bindings: HashMap::new(),
+                global: parent.is_none(),
+                parent,
+            })),
+            modules,
+        }
+    }
+    
+    /// Creates a new, empty frame whose parent is this environment.
+    pub fn extend(&self) -> Self {
+        Self::with_parent(Some(self.clone()), self.modules.clone())
+    }
+    
+    /// Returns the global frame this environment is nested in, where `def`
+    /// binds names even from inside a function or `let`.
+    pub fn global(&self) -> Environment {
+        let frame = self.frame.borrow();
+        match &frame.parent {
+            Some(parent) if !frame.global => parent.global(),
+            _ => self.clone(),
+        }
+    }
+    
+    /// Binds `name` in this frame, shadowing any binding in enclosing frames.
+    pub fn define(&self, name: &str, value: Value) {
+        self.frame.borrow_mut().bindings.insert(name.to_string(), value);
+    }
+    
+    /// Rebinds `name` in the nearest frame that binds it. Returns false if
+    /// no frame does.
+    pub fn set(&self, name: &str, value: Value) -> bool {
+        let mut frame = self.frame.borrow_mut();
+        if let Some(slot) = frame.bindings.get_mut(name) {
+            *slot = value;
+            return true;
+        }
+        
+        match &frame.parent {
+            Some(parent) => parent.set(name, value),
+            None => false,
+        }
+    }
+    
+    /// Looks `name` up in this frame, then in each enclosing frame in turn.
+    pub fn lookup(&self, name: &str) -> Option<Value> {
+        let frame = self.frame.borrow();
+        match frame.bindings.get(name) {
+            Some(value) => Some(value.clone()),
+            None => frame.parent.as_ref().and_then(|parent| parent.lookup(name)),
+        }
+    }
+    
+    /// Returns every name visible from this environment, sorted and without
+    /// duplicates.
+    pub fn names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut env = Some(self.clone());
+        
+        while let Some(current) = env {
+            let frame = current.frame.borrow();
+            names.extend(frame.bindings.keys().cloned());
+            env = frame.parent.clone();
+        }
+        
+        names.sort();
+        names.dedup();
+        names
     }
     
-    pub fn lookup(&self, name: &str) -> Option<&Value> {
-        self.bindings.get(name)
+    /// Returns the bindings made in this frame alone, sorted by name.
+    pub fn own_bindings(&self) -> Vec<(String, Value)> {
+        let mut bindings: Vec<_> = self.frame.borrow().bindings.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        bindings
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Frames can (and usually do) contain closures that point back at themselves,
+// so don't try to print the bindings.
+impl std::fmt::Debug for Environment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Environment").finish_non_exhaustive()
     }
 }
 
 // Built-in arithmetic functions
 fn builtin_add(args: &[Value]) -> Result<Value, EvalError> {
-    let mut sum = 0.0;
+    let mut sum = Number::Int(0);
     for arg in args {
         match arg {
-            Value::Number(n) => sum += n,
-            _ => return Err(EvalError::TypeError("+ requires numbers".to_string())),
+            Value::Number(n) => sum = &sum + n,
+            _ => return Err(ErrorKind::TypeError("+ requires numbers".to_string()).into()),
         }
     }
     Ok(Value::Number(sum))
@@ -103,7 +610,7 @@ fn builtin_add(args: &[Value]) -> Result<Value, EvalError> {
 
 fn builtin_subtract(args: &[Value]) -> Result<Value, EvalError> {
     if args.is_empty() {
-        return Err(EvalError::ArityError("- requires at least 1 argument".to_string()));
+        return Err(ErrorKind::ArityError("- requires at least 1 argument".to_string()).into());
     }
     
     match &args[0] {
@@ -111,129 +618,111 @@ fn builtin_subtract(args: &[Value]) -> Result<Value, EvalError> {
             if args.len() == 1 {
                 Ok(Value::Number(-first))
             } else {
-                let mut result = *first;
+                let mut result = first.clone();
                 for arg in &args[1..] {
                     match arg {
-                        Value::Number(n) => result -= n,
-                        _ => return Err(EvalError::TypeError("- requires numbers".to_string())),
+                        Value::Number(n) => result = &result - n,
+                        _ => return Err(ErrorKind::TypeError("- requires numbers".to_string()).into()),
                     }
                 }
                 Ok(Value::Number(result))
             }
         }
-        _ => Err(EvalError::TypeError("- requires numbers".to_string())),
+        _ => Err(ErrorKind::TypeError("- requires numbers".to_string()).into()),
     }
 }
 
 fn builtin_multiply(args: &[Value]) -> Result<Value, EvalError> {
-    let mut product = 1.0;
+    let mut product = Number::Int(1);
     for arg in args {
         match arg {
-            Value::Number(n) => product *= n,
-            _ => return Err(EvalError::TypeError("* requires numbers".to_string())),
+            Value::Number(n) => product = &product * n,
+            _ => return Err(ErrorKind::TypeError("* requires numbers".to_string()).into()),
         }
     }
     Ok(Value::Number(product))
 }
 
+// Dividing integers gives an exact rational, e.g. (/ 1 3) => 1/3
 fn builtin_divide(args: &[Value]) -> Result<Value, EvalError> {
     if args.is_empty() {
-        return Err(EvalError::ArityError("/ requires at least 1 argument".to_string()));
+        return Err(ErrorKind::ArityError("/ requires at least 1 argument".to_string()).into());
     }
     
     match &args[0] {
         Value::Number(first) => {
             if args.len() == 1 {
-                if *first == 0.0 {
-                    return Err(EvalError::DivisionByZero);
-                }
-                Ok(Value::Number(1.0 / first))
+                let result = Number::Int(1).checked_div(first).ok_or(ErrorKind::DivisionByZero)?;
+                Ok(Value::Number(result))
             } else {
-                let mut result = *first;
+                let mut result = first.clone();
                 for arg in &args[1..] {
                     match arg {
                         Value::Number(n) => {
-                            if *n == 0.0 {
-                                return Err(EvalError::DivisionByZero);
-                            }
-                            result /= n;
+                            result = result.checked_div(n).ok_or(ErrorKind::DivisionByZero)?;
                         }
-                        _ => return Err(EvalError::TypeError("/ requires numbers".to_string())),
+                        _ => return Err(ErrorKind::TypeError("/ requires numbers".to_string()).into()),
                     }
                 }
                 Ok(Value::Number(result))
             }
         }
-        _ => Err(EvalError::TypeError("/ requires numbers".to_string())),
+        _ => Err(ErrorKind::TypeError("/ requires numbers".to_string()).into()),
     }
 }
 
 fn builtin_equal(args: &[Value]) -> Result<Value, EvalError> {
-    if args.len() != 2 {
-        return Err(EvalError::ArityError("= requires exactly 2 arguments".to_string()));
+    if args.len() < 2 {
+        return Err(ErrorKind::ArityError("= requires at least 2 arguments".to_string()).into());
     }
     
-    let result = match (&args[0], &args[1]) {
-        (Value::Number(a), Value::Number(b)) => a == b,
-        (Value::String(a), Value::String(b)) => a == b,
-        (Value::Symbol(a), Value::Symbol(b)) => a == b,
-        (Value::Boolean(a), Value::Boolean(b)) => a == b,
-        (Value::Nil, Value::Nil) => true,
-        _ => false,
-    };
-    
+    let result = args.windows(2).all(|pair| pair[0] == pair[1]);
     Ok(Value::Boolean(result))
 }
 
 fn builtin_less_than(args: &[Value]) -> Result<Value, EvalError> {
-    if args.len() != 2 {
-        return Err(EvalError::ArityError("< requires exactly 2 arguments".to_string()));
-    }
-    
-    match (&args[0], &args[1]) {
-        (Value::Number(a), Value::Number(b)) => {
-            Ok(Value::Number(if a < b { 1.0 } else { 0.0 }))
-        }
-        _ => Err(EvalError::TypeError("< requires numbers".to_string())),
-    }
+    compare_chain("<", args, Ordering::is_lt)
 }
 
 fn builtin_greater_than(args: &[Value]) -> Result<Value, EvalError> {
-    if args.len() != 2 {
-        return Err(EvalError::ArityError("> requires exactly 2 arguments".to_string()));
-    }
-    
-    match (&args[0], &args[1]) {
-        (Value::Number(a), Value::Number(b)) => {
-            Ok(Value::Number(if a > b { 1.0 } else { 0.0 }))
-        }
-        _ => Err(EvalError::TypeError("> requires numbers".to_string())),
-    }
+    compare_chain(">", args, Ordering::is_gt)
 }
 
 fn builtin_less_than_or_equal(args: &[Value]) -> Result<Value, EvalError> {
-    if args.len() != 2 {
-        return Err(EvalError::ArityError("<= requires exactly 2 arguments".to_string()));
-    }
-    
-    match (&args[0], &args[1]) {
-        (Value::Number(a), Value::Number(b)) => {
-            Ok(Value::Number(if a <= b { 1.0 } else { 0.0 }))
-        }
-        _ => Err(EvalError::TypeError("<= requires numbers".to_string())),
-    }
+    compare_chain("<=", args, Ordering::is_le)
 }
 
 fn builtin_greater_than_or_equal(args: &[Value]) -> Result<Value, EvalError> {
-    if args.len() != 2 {
-        return Err(EvalError::ArityError(">= requires exactly 2 arguments".to_string()));
+    compare_chain(">=", args, Ordering::is_ge)
+}
+
+/// Checks that each argument stands in the relation `holds` to the next, so
+/// `(< a b c)` means `a < b` and `b < c`.
+fn compare_chain(name: &str, args: &[Value], holds: fn(Ordering) -> bool) -> Result<Value, EvalError> {
+    if args.len() < 2 {
+        return Err(ErrorKind::ArityError(format!("{} requires at least 2 arguments", name)).into());
     }
     
-    match (&args[0], &args[1]) {
-        (Value::Number(a), Value::Number(b)) => {
-            Ok(Value::Number(if a >= b { 1.0 } else { 0.0 }))
-        }
-        _ => Err(EvalError::TypeError(">= requires numbers".to_string())),
+    let mut result = true;
+    for pair in args.windows(2) {
+        result &= compare_values(name, &pair[0], &pair[1])?.is_some_and(holds);
+    }
+    Ok(Value::Boolean(result))
+}
+
+/// Orders numbers numerically and strings and symbols lexicographically.
+/// Returns `None` for values that have no order, such as NaN.
+fn compare_values(name: &str, a: &Value, b: &Value) -> Result<Option<Ordering>, EvalError> {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => Ok(a.partial_cmp(b)),
+        (Value::String(a), Value::String(b)) | (Value::Symbol(a), Value::Symbol(b)) => Ok(Some(a.cmp(b))),
+        (Value::Char(a), Value::Char(b)) => Ok(Some(a.cmp(b))),
+        _ => Err(ErrorKind::TypeError(format!(
+            "{} requires numbers, strings, chars or symbols of the same type, got {} and {}",
+            name,
+            a.type_name(),
+            b.type_name()
+        )).into()),
     }
 }
 
@@ -243,7 +732,7 @@ fn builtin_list(args: &[Value]) -> Result<Value, EvalError> {
 
 fn builtin_car(args: &[Value]) -> Result<Value, EvalError> {
     if args.len() != 1 {
-        return Err(EvalError::ArityError("car requires exactly 1 argument".to_string()));
+        return Err(ErrorKind::ArityError("car requires exactly 1 argument".to_string()).into());
     }
     
     match &args[0] {
@@ -254,13 +743,13 @@ fn builtin_car(args: &[Value]) -> Result<Value, EvalError> {
                 Ok(list[0].clone())
             }
         }
-        _ => Err(EvalError::TypeError("car requires a list".to_string())),
+        _ => Err(ErrorKind::TypeError("car requires a list".to_string()).into()),
     }
 }
 
 fn builtin_cdr(args: &[Value]) -> Result<Value, EvalError> {
     if args.len() != 1 {
-        return Err(EvalError::ArityError("cdr requires exactly 1 argument".to_string()));
+        return Err(ErrorKind::ArityError("cdr requires exactly 1 argument".to_string()).into());
     }
     
     match &args[0] {
@@ -271,13 +760,13 @@ fn builtin_cdr(args: &[Value]) -> Result<Value, EvalError> {
                 Ok(Value::List(list[1..].to_vec()))
             }
         }
-        _ => Err(EvalError::TypeError("cdr requires a list".to_string())),
+        _ => Err(ErrorKind::TypeError("cdr requires a list".to_string()).into()),
     }
 }
 
 fn builtin_cons(args: &[Value]) -> Result<Value, EvalError> {
     if args.len() != 2 {
-        return Err(EvalError::ArityError("cons requires exactly 2 arguments".to_string()));
+        return Err(ErrorKind::ArityError("cons requires exactly 2 arguments".to_string()).into());
     }
     
     match &args[1] {
@@ -287,25 +776,249 @@ fn builtin_cons(args: &[Value]) -> Result<Value, EvalError> {
             Ok(Value::List(new_list))
         }
         Value::Nil => Ok(Value::List(vec![args[0].clone()])),
-        _ => Err(EvalError::TypeError("cons requires second argument to be a list".to_string())),
+        _ => Err(ErrorKind::TypeError("cons requires second argument to be a list".to_string()).into()),
     }
 }
 
 fn builtin_length(args: &[Value]) -> Result<Value, EvalError> {
     if args.len() != 1 {
-        return Err(EvalError::ArityError("length requires exactly 1 argument".to_string()));
+        return Err(ErrorKind::ArityError("length requires exactly 1 argument".to_string()).into());
+    }
+    
+    match &args[0] {
+        Value::List(list) => Ok(Value::Number(Number::from(list.len() as i64))),
+        Value::String(s) => Ok(Value::Number(Number::from(s.chars().count() as i64))),
+        _ => Err(ErrorKind::TypeError("length requires a list or string".to_string()).into()),
+    }
+}
+
+/// Extracts a string argument, or fails with a type error naming the builtin.
+fn expect_string<'a>(name: &str, value: &'a Value) -> Result<&'a str, EvalError> {
+    match value {
+        Value::String(s) => Ok(s),
+        other => Err(ErrorKind::TypeError(format!("{} requires a string, got {}", name, other.type_name())).into()),
+    }
+}
+
+/// Extracts a character position, counted in Unicode scalar values.
+fn expect_position(name: &str, value: &Value) -> Result<usize, EvalError> {
+    as_index(value).ok_or_else(|| {
+        ErrorKind::TypeError(format!("{} requires a non-negative integer position, got {}", name, value)).into()
+    })
+}
+
+/// The text to search for in a string: a string or a single character.
+fn expect_pattern(name: &str, value: &Value) -> Result<String, EvalError> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Char(ch) => Ok(ch.to_string()),
+        other => Err(ErrorKind::TypeError(format!("{} requires a string or char to search for, got {}", name, other.type_name())).into()),
+    }
+}
+
+/// Converts a byte offset into `s` to a character position.
+fn char_position(s: &str, byte_offset: usize) -> usize {
+    s[..byte_offset].chars().count()
+}
+
+fn builtin_substring(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() < 2 || args.len() > 3 {
+        return Err(ErrorKind::ArityError("substring requires 2 or 3 arguments (string, start, optional end)".to_string()).into());
+    }
+    
+    let s = expect_string("substring", &args[0])?;
+    let length = s.chars().count();
+    let start = expect_position("substring", &args[1])?;
+    let end = match args.get(2) {
+        Some(end) => expect_position("substring", end)?,
+        None => length,
+    };
+    
+    if start > end || end > length {
+        return Err(ErrorKind::IndexError(format!(
+            "substring range {} to {} out of bounds for string of length {}",
+            start, end, length
+        )).into());
+    }
+    
+    Ok(Value::String(s.chars().skip(start).take(end - start).collect()))
+}
+
+fn builtin_string_append(args: &[Value]) -> Result<Value, EvalError> {
+    let mut result = String::new();
+    for arg in args {
+        match arg {
+            Value::String(s) => result.push_str(s),
+            Value::Char(ch) => result.push(*ch),
+            other => return Err(ErrorKind::TypeError(format!("string-append requires strings or chars, got {}", other.type_name())).into()),
+        }
+    }
+    Ok(Value::String(result))
+}
+
+fn builtin_string_split(args: &[Value]) -> Result<Value, EvalError> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(ErrorKind::ArityError("string-split requires 1 or 2 arguments (string, optional separator)".to_string()).into());
+    }
+    
+    let s = expect_string("string-split", &args[0])?;
+    let parts: Vec<Value> = match args.get(1) {
+        // Without a separator, split on runs of whitespace
+        None => s.split_whitespace().map(|part| Value::String(part.to_string())).collect(),
+        Some(separator) => {
+            let separator = expect_pattern("string-split", separator)?;
+            if separator.is_empty() {
+                s.chars().map(|ch| Value::String(ch.to_string())).collect()
+            } else {
+                s.split(separator.as_str()).map(|part| Value::String(part.to_string())).collect()
+            }
+        }
+    };
+    
+    Ok(Value::List(parts))
+}
+
+fn builtin_string_join(args: &[Value]) -> Result<Value, EvalError> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(ErrorKind::ArityError("string-join requires 1 or 2 arguments (list of strings, optional separator)".to_string()).into());
+    }
+    
+    let parts = match &args[0] {
+        Value::List(parts) | Value::Vector(parts) => parts.as_slice(),
+        Value::Nil => &[],
+        other => return Err(ErrorKind::TypeError(format!("string-join requires a list of strings, got {}", other.type_name())).into()),
+    };
+    let separator = match args.get(1) {
+        Some(separator) => expect_pattern("string-join", separator)?,
+        None => String::new(),
+    };
+    
+    let strings = parts.iter().map(|part| expect_string("string-join", part)).collect::<Result<Vec<_>, _>>()?;
+    Ok(Value::String(strings.join(&separator)))
+}
+
+fn builtin_string_upcase(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("string-upcase requires exactly 1 argument".to_string()).into());
+    }
+    
+    Ok(Value::String(expect_string("string-upcase", &args[0])?.to_uppercase()))
+}
+
+fn builtin_string_downcase(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("string-downcase requires exactly 1 argument".to_string()).into());
+    }
+    
+    Ok(Value::String(expect_string("string-downcase", &args[0])?.to_lowercase()))
+}
+
+fn builtin_string_trim(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("string-trim requires exactly 1 argument".to_string()).into());
+    }
+    
+    Ok(Value::String(expect_string("string-trim", &args[0])?.trim().to_string()))
+}
+
+fn builtin_string_index(args: &[Value], env: &Environment) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(ErrorKind::ArityError("string-index requires exactly 2 arguments".to_string()).into());
+    }
+    
+    let s = expect_string("string-index", &args[0])?;
+    
+    // The needle is a string or char to find, or a predicate on chars
+    let position = match &args[1] {
+        Value::Function(_) | Value::EnvFunction(_) | Value::Lambda { .. } | Value::Keyword(_) => {
+            let mut found = None;
+            for (i, ch) in s.chars().enumerate() {
+                if is_truthy(&apply(&args[1], vec![Value::Char(ch)], env)?) {
+                    found = Some(i);
+                    break;
+                }
+            }
+            found
+        }
+        needle => {
+            let needle = expect_pattern("string-index", needle)?;
+            s.find(needle.as_str()).map(|offset| char_position(s, offset))
+        }
+    };
+    
+    Ok(position.map_or(Value::Nil, |i| Value::Number(Number::from(i as i64))))
+}
+
+fn builtin_string_to_list(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("string->list requires exactly 1 argument".to_string()).into());
+    }
+    
+    Ok(Value::List(expect_string("string->list", &args[0])?.chars().map(Value::Char).collect()))
+}
+
+fn builtin_list_to_string(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("list->string requires exactly 1 argument".to_string()).into());
+    }
+    
+    let chars = match &args[0] {
+        Value::List(chars) | Value::Vector(chars) => chars.as_slice(),
+        Value::Nil => &[],
+        other => return Err(ErrorKind::TypeError(format!("list->string requires a list of chars, got {}", other.type_name())).into()),
+    };
+    
+    let mut result = String::new();
+    for ch in chars {
+        match ch {
+            Value::Char(ch) => result.push(*ch),
+            other => return Err(ErrorKind::TypeError(format!("list->string requires a list of chars, got {}", other.type_name())).into()),
+        }
+    }
+    Ok(Value::String(result))
+}
+
+fn builtin_string_to_number(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("string->number requires exactly 1 argument".to_string()).into());
+    }
+    
+    // Accepts exactly the numeric literal syntax; anything else gives nil
+    match tokenizer::parse_number(expect_string("string->number", &args[0])?) {
+        Ok(n) => Ok(Value::Number(n)),
+        Err(_) => Ok(Value::Nil),
+    }
+}
+
+fn builtin_number_to_string(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("number->string requires exactly 1 argument".to_string()).into());
     }
     
     match &args[0] {
-        Value::List(list) => Ok(Value::Number(list.len() as f64)),
-        Value::String(s) => Ok(Value::Number(s.len() as f64)),
-        _ => Err(EvalError::TypeError("length requires a list or string".to_string())),
+        Value::Number(n) => Ok(Value::String(n.to_string())),
+        other => Err(ErrorKind::TypeError(format!("number->string requires a number, got {}", other.type_name())).into()),
+    }
+}
+
+fn builtin_string_replace(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 3 {
+        return Err(ErrorKind::ArityError("string-replace requires exactly 3 arguments (string, pattern, replacement)".to_string()).into());
+    }
+    
+    let s = expect_string("string-replace", &args[0])?;
+    let pattern = expect_pattern("string-replace", &args[1])?;
+    let replacement = expect_pattern("string-replace", &args[2])?;
+    if pattern.is_empty() {
+        return Err(ErrorKind::TypeError("string-replace requires a non-empty pattern".to_string()).into());
     }
+    
+    Ok(Value::String(s.replace(pattern.as_str(), &replacement)))
 }
 
 fn builtin_null(args: &[Value]) -> Result<Value, EvalError> {
     if args.len() != 1 {
-        return Err(EvalError::ArityError("null? requires exactly 1 argument".to_string()));
+        return Err(ErrorKind::ArityError("null? requires exactly 1 argument".to_string()).into());
     }
     
     let result = match &args[0] {
@@ -319,7 +1032,7 @@ fn builtin_null(args: &[Value]) -> Result<Value, EvalError> {
 
 fn builtin_reverse(args: &[Value]) -> Result<Value, EvalError> {
     if args.len() != 1 {
-        return Err(EvalError::ArityError("reverse requires exactly 1 argument".to_string()));
+        return Err(ErrorKind::ArityError("reverse requires exactly 1 argument".to_string()).into());
     }
     
     match &args[0] {
@@ -329,7 +1042,7 @@ fn builtin_reverse(args: &[Value]) -> Result<Value, EvalError> {
             Ok(Value::List(reversed))
         }
         Value::Nil => Ok(Value::List(vec![])),
-        _ => Err(EvalError::TypeError("reverse requires a list".to_string())),
+        _ => Err(ErrorKind::TypeError("reverse requires a list".to_string()).into()),
     }
 }
 
@@ -340,6 +1053,7 @@ fn builtin_print(args: &[Value]) -> Result<Value, EvalError> {
         }
         match arg {
             Value::String(s) => print!("{}", s),  // Print strings without quotes
+            Value::Char(ch) => print!("{}", ch),
             other => print!("{}", other),
         }
     }
@@ -349,329 +1063,1786 @@ fn builtin_print(args: &[Value]) -> Result<Value, EvalError> {
 
 fn builtin_min(args: &[Value]) -> Result<Value, EvalError> {
     if args.is_empty() {
-        return Err(EvalError::ArityError("min requires at least 1 argument".to_string()));
+        return Err(ErrorKind::ArityError("min requires at least 1 argument".to_string()).into());
     }
     
     let mut min_val = match &args[0] {
-        Value::Number(n) => *n,
-        _ => return Err(EvalError::TypeError("min requires numbers".to_string())),
+        Value::Number(n) => n,
+        _ => return Err(ErrorKind::TypeError("min requires numbers".to_string()).into()),
     };
     
     for arg in &args[1..] {
         match arg {
             Value::Number(n) => {
-                if *n < min_val {
-                    min_val = *n;
+                if n < min_val {
+                    min_val = n;
                 }
             }
-            _ => return Err(EvalError::TypeError("min requires numbers".to_string())),
+            _ => return Err(ErrorKind::TypeError("min requires numbers".to_string()).into()),
         }
     }
     
-    Ok(Value::Number(min_val))
+    Ok(Value::Number(min_val.clone()))
 }
 
 fn builtin_max(args: &[Value]) -> Result<Value, EvalError> {
     if args.is_empty() {
-        return Err(EvalError::ArityError("max requires at least 1 argument".to_string()));
+        return Err(ErrorKind::ArityError("max requires at least 1 argument".to_string()).into());
     }
     
     let mut max_val = match &args[0] {
-        Value::Number(n) => *n,
-        _ => return Err(EvalError::TypeError("max requires numbers".to_string())),
+        Value::Number(n) => n,
+        _ => return Err(ErrorKind::TypeError("max requires numbers".to_string()).into()),
     };
     
     for arg in &args[1..] {
         match arg {
             Value::Number(n) => {
-                if *n > max_val {
-                    max_val = *n;
+                if n > max_val {
+                    max_val = n;
                 }
             }
-            _ => return Err(EvalError::TypeError("max requires numbers".to_string())),
+            _ => return Err(ErrorKind::TypeError("max requires numbers".to_string()).into()),
         }
     }
     
-    Ok(Value::Number(max_val))
+    Ok(Value::Number(max_val.clone()))
 }
 
 fn builtin_abs(args: &[Value]) -> Result<Value, EvalError> {
     if args.len() != 1 {
-        return Err(EvalError::ArityError("abs requires exactly 1 argument".to_string()));
+        return Err(ErrorKind::ArityError("abs requires exactly 1 argument".to_string()).into());
     }
     
     match &args[0] {
         Value::Number(n) => Ok(Value::Number(n.abs())),
-        _ => Err(EvalError::TypeError("abs requires a number".to_string())),
+        _ => Err(ErrorKind::TypeError("abs requires a number".to_string()).into()),
     }
 }
 
 fn builtin_mod(args: &[Value]) -> Result<Value, EvalError> {
     if args.len() != 2 {
-        return Err(EvalError::ArityError("mod requires exactly 2 arguments".to_string()));
+        return Err(ErrorKind::ArityError("mod requires exactly 2 arguments".to_string()).into());
     }
     
     match (&args[0], &args[1]) {
         (Value::Number(a), Value::Number(b)) => {
-            if *b == 0.0 {
-                return Err(EvalError::DivisionByZero);
-            }
-            Ok(Value::Number(a % b))
+            let result = a.checked_rem(b).ok_or(ErrorKind::DivisionByZero)?;
+            Ok(Value::Number(result))
         }
-        _ => Err(EvalError::TypeError("mod requires numbers".to_string())),
+        _ => Err(ErrorKind::TypeError("mod requires numbers".to_string()).into()),
     }
 }
 
 fn builtin_not(args: &[Value]) -> Result<Value, EvalError> {
     if args.len() != 1 {
-        return Err(EvalError::ArityError("not requires exactly 1 argument".to_string()));
+        return Err(ErrorKind::ArityError("not requires exactly 1 argument".to_string()).into());
     }
     
     let result = !is_truthy(&args[0]);
     Ok(Value::Boolean(result))
 }
 
-fn builtin_and(args: &[Value]) -> Result<Value, EvalError> {
-    // Short-circuiting: return false if any argument is falsy
-    for arg in args {
-        if !is_truthy(arg) {
-            return Ok(Value::Boolean(false));
-        }
+fn builtin_box(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("box requires exactly 1 argument".to_string()).into());
     }
-    // All arguments are truthy
-    Ok(Value::Boolean(true))
+    
+    Ok(Value::Box(Rc::new(RefCell::new(args[0].clone()))))
+}
+
+fn builtin_unbox(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("unbox requires exactly 1 argument".to_string()).into());
+    }
+    
+    match &args[0] {
+        Value::Box(cell) => Ok(cell.borrow().clone()),
+        _ => Err(ErrorKind::TypeError("unbox requires a box".to_string()).into()),
+    }
+}
+
+fn builtin_set_box(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(ErrorKind::ArityError("set-box! requires exactly 2 arguments".to_string()).into());
+    }
+    
+    match &args[0] {
+        Value::Box(cell) => {
+            *cell.borrow_mut() = args[1].clone();
+            Ok(args[1].clone())
+        }
+        _ => Err(ErrorKind::TypeError("set-box! requires a box as first argument".to_string()).into()),
+    }
+}
+
+fn builtin_is_box(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("box? requires exactly 1 argument".to_string()).into());
+    }
+    
+    Ok(Value::Boolean(matches!(args[0], Value::Box(_))))
+}
+
+/// Converts `value` to an index, if it is a non-negative integer that fits.
+fn as_index(value: &Value) -> Option<usize> {
+    match value {
+        Value::Number(n) => n.to_i64().and_then(|i| usize::try_from(i).ok()),
+        _ => None,
+    }
+}
+
+fn builtin_get(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() < 2 || args.len() > 3 {
+        return Err(ErrorKind::ArityError("get requires 2 or 3 arguments (collection, key, optional default)".to_string()).into());
+    }
+    
+    let found = match &args[0] {
+        Value::Map(map) => map.get(&args[1]).cloned(),
+        Value::Set(set) => set.contains(&args[1]).then(|| args[1].clone()),
+        Value::List(elements) | Value::Vector(elements) => as_index(&args[1]).and_then(|i| elements.get(i).cloned()),
+        Value::Nil => None,
+        other => return Err(ErrorKind::TypeError(format!("get requires a map, set, list or vector, got {}", other.type_name())).into()),
+    };
+    
+    Ok(found.unwrap_or_else(|| args.get(2).cloned().unwrap_or(Value::Nil)))
+}
+
+fn builtin_assoc(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() == 2 {
+        return assoc_list(&args[0], &args[1]);
+    }
+    if args.len() < 3 || args.len().is_multiple_of(2) {
+        return Err(ErrorKind::ArityError(
+            "assoc requires a key and an association list, or a collection followed by key-value pairs".to_string()
+        ).into());
+    }
+    
+    match &args[0] {
+        Value::Map(map) => {
+            let mut map = map.clone();
+            for pair in args[1..].chunks(2) {
+                map.insert(pair[0].clone(), pair[1].clone());
+            }
+            Ok(Value::Map(map))
+        }
+        Value::Nil => Ok(Value::Map(Box::new(args[1..].chunks(2).map(|pair| (pair[0].clone(), pair[1].clone())).collect()))),
+        Value::Vector(elements) => {
+            let mut elements = elements.clone();
+            for pair in args[1..].chunks(2) {
+                // Assigning just past the end appends
+                match as_index(&pair[0]) {
+                    Some(i) if i < elements.len() => elements[i] = pair[1].clone(),
+                    Some(i) if i == elements.len() => elements.push(pair[1].clone()),
+                    _ => return Err(ErrorKind::IndexError(format!(
+                        "assoc index {} out of bounds for vector of length {}",
+                        pair[0],
+                        elements.len()
+                    )).into()),
+                }
+            }
+            Ok(Value::Vector(elements))
+        }
+        other => Err(ErrorKind::TypeError(format!("assoc requires a map or vector, got {}", other.type_name())).into()),
+    }
+}
+
+fn builtin_dissoc(args: &[Value]) -> Result<Value, EvalError> {
+    if args.is_empty() {
+        return Err(ErrorKind::ArityError("dissoc requires at least 1 argument".to_string()).into());
+    }
+    
+    match &args[0] {
+        Value::Map(map) => {
+            let mut map = map.clone();
+            for key in &args[1..] {
+                map.remove(key);
+            }
+            Ok(Value::Map(map))
+        }
+        Value::Set(set) => {
+            let mut set = set.clone();
+            for value in &args[1..] {
+                set.remove(value);
+            }
+            Ok(Value::Set(set))
+        }
+        Value::Nil => Ok(Value::Nil),
+        other => Err(ErrorKind::TypeError(format!("dissoc requires a map or set, got {}", other.type_name())).into()),
+    }
+}
+
+fn builtin_keys(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("keys requires exactly 1 argument".to_string()).into());
+    }
+    
+    match &args[0] {
+        Value::Map(map) => Ok(Value::List(map.keys().cloned().collect())),
+        Value::Nil => Ok(Value::List(vec![])),
+        other => Err(ErrorKind::TypeError(format!("keys requires a map, got {}", other.type_name())).into()),
+    }
+}
+
+fn builtin_vals(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("vals requires exactly 1 argument".to_string()).into());
+    }
+    
+    match &args[0] {
+        Value::Map(map) => Ok(Value::List(map.values().cloned().collect())),
+        Value::Nil => Ok(Value::List(vec![])),
+        other => Err(ErrorKind::TypeError(format!("vals requires a map, got {}", other.type_name())).into()),
+    }
+}
+
+fn builtin_contains(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(ErrorKind::ArityError("contains? requires exactly 2 arguments".to_string()).into());
+    }
+    
+    let result = match &args[0] {
+        Value::Map(map) => map.contains_key(&args[1]),
+        Value::Set(set) => set.contains(&args[1]),
+        // Like get, lists and vectors are keyed by index
+        Value::List(elements) | Value::Vector(elements) => as_index(&args[1]).is_some_and(|i| i < elements.len()),
+        Value::Nil => false,
+        other => return Err(ErrorKind::TypeError(format!("contains? requires a map, set, list or vector, got {}", other.type_name())).into()),
+    };
+    
+    Ok(Value::Boolean(result))
+}
+
+fn builtin_nth(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() < 2 || args.len() > 3 {
+        return Err(ErrorKind::ArityError("nth requires 2 or 3 arguments (sequence, index, optional default)".to_string()).into());
+    }
+    
+    let elements = match &args[0] {
+        Value::List(elements) | Value::Vector(elements) => elements.as_slice(),
+        Value::Nil => &[],
+        other => return Err(ErrorKind::TypeError(format!("nth requires a list or vector, got {}", other.type_name())).into()),
+    };
+    let Value::Number(n) = &args[1] else {
+        return Err(ErrorKind::TypeError(format!("nth requires an integer index, got {}", args[1].type_name())).into());
+    };
+    if !n.is_integer() {
+        return Err(ErrorKind::TypeError(format!("nth requires an integer index, got {}", n)).into());
+    }
+    
+    match (as_index(&args[1]).and_then(|i| elements.get(i)), args.get(2)) {
+        (Some(value), _) => Ok(value.clone()),
+        (None, Some(default)) => Ok(default.clone()),
+        (None, None) => Err(ErrorKind::IndexError(format!(
+            "nth index {} out of bounds for {} of length {}",
+            n,
+            args[0].type_name(),
+            elements.len()
+        )).into()),
+    }
+}
+
+fn builtin_conj(args: &[Value]) -> Result<Value, EvalError> {
+    if args.is_empty() {
+        return Err(ErrorKind::ArityError("conj requires at least 1 argument".to_string()).into());
+    }
+    
+    let items = &args[1..];
+    match &args[0] {
+        // Each collection grows where adding is cheapest: lists at the front,
+        // vectors at the back
+        Value::List(elements) => {
+            let mut list: Vec<Value> = items.iter().rev().cloned().collect();
+            list.extend(elements.iter().cloned());
+            Ok(Value::List(list))
+        }
+        Value::Nil => Ok(Value::List(items.iter().rev().cloned().collect())),
+        Value::Vector(elements) => {
+            let mut elements = elements.clone();
+            elements.extend(items.iter().cloned());
+            Ok(Value::Vector(elements))
+        }
+        Value::Set(set) => {
+            let mut set = set.clone();
+            for item in items {
+                set.insert(item.clone());
+            }
+            Ok(Value::Set(set))
+        }
+        Value::Map(map) => {
+            let mut map = map.clone();
+            for item in items {
+                match item {
+                    Value::Vector(pair) if pair.len() == 2 => map.insert(pair[0].clone(), pair[1].clone()),
+                    Value::Map(other) => {
+                        for (key, value) in other.iter() {
+                            map.insert(key.clone(), value.clone());
+                        }
+                    }
+                    other => return Err(ErrorKind::TypeError(format!(
+                        "conj onto a map requires [key value] vectors or maps, got {}",
+                        other
+                    )).into()),
+                }
+            }
+            Ok(Value::Map(map))
+        }
+        other => Err(ErrorKind::TypeError(format!("conj requires a collection, got {}", other.type_name())).into()),
+    }
+}
+
+/// Extracts the elements of a list argument. Vectors are accepted too, and
+/// nil counts as the empty list.
+fn expect_list<'a>(name: &str, value: &'a Value) -> Result<&'a [Value], EvalError> {
+    match value {
+        Value::List(elements) | Value::Vector(elements) => Ok(elements),
+        Value::Nil => Ok(&[]),
+        other => Err(ErrorKind::TypeError(format!("{} requires a list, got {}", name, other.type_name())).into()),
+    }
+}
+
+/// Groups the elements of several lists by position, stopping at the end of
+/// the shortest: ((a b) (1 2)) gives [[a 1] [b 2]].
+fn transpose(name: &str, lists: &[Value]) -> Result<Vec<Vec<Value>>, EvalError> {
+    let lists = lists.iter().map(|list| expect_list(name, list)).collect::<Result<Vec<_>, _>>()?;
+    let length = lists.iter().map(|list| list.len()).min().unwrap_or(0);
+    Ok((0..length).map(|i| lists.iter().map(|list| list[i].clone()).collect()).collect())
 }
 
-fn builtin_or(args: &[Value]) -> Result<Value, EvalError> {
-    // Short-circuiting: return true if any argument is truthy
+fn builtin_map(args: &[Value], env: &Environment) -> Result<Value, EvalError> {
+    if args.len() < 2 {
+        return Err(ErrorKind::ArityError("map requires a function and at least 1 list".to_string()).into());
+    }
+    
+    let mut results = Vec::new();
+    for call_args in transpose("map", &args[1..])? {
+        results.push(apply(&args[0], call_args, env)?);
+    }
+    Ok(Value::List(results))
+}
+
+fn builtin_for_each(args: &[Value], env: &Environment) -> Result<Value, EvalError> {
+    if args.len() < 2 {
+        return Err(ErrorKind::ArityError("for-each requires a function and at least 1 list".to_string()).into());
+    }
+    
+    for call_args in transpose("for-each", &args[1..])? {
+        apply(&args[0], call_args, env)?;
+    }
+    Ok(Value::Nil)
+}
+
+fn builtin_filter(args: &[Value], env: &Environment) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(ErrorKind::ArityError("filter requires exactly 2 arguments".to_string()).into());
+    }
+    
+    let mut results = Vec::new();
+    for item in expect_list("filter", &args[1])? {
+        if is_truthy(&apply(&args[0], vec![item.clone()], env)?) {
+            results.push(item.clone());
+        }
+    }
+    Ok(Value::List(results))
+}
+
+fn builtin_fold_left(args: &[Value], env: &Environment) -> Result<Value, EvalError> {
+    if args.len() < 3 {
+        return Err(ErrorKind::ArityError("fold-left requires a function, an initial value and at least 1 list".to_string()).into());
+    }
+    
+    // (fold-left f init '(a b)) => (f (f init a) b)
+    let mut acc = args[1].clone();
+    for items in transpose("fold-left", &args[2..])? {
+        let mut call_args = vec![acc];
+        call_args.extend(items);
+        acc = apply(&args[0], call_args, env)?;
+    }
+    Ok(acc)
+}
+
+fn builtin_fold_right(args: &[Value], env: &Environment) -> Result<Value, EvalError> {
+    if args.len() < 3 {
+        return Err(ErrorKind::ArityError("fold-right requires a function, an initial value and at least 1 list".to_string()).into());
+    }
+    
+    // (fold-right f init '(a b)) => (f a (f b init))
+    let mut acc = args[1].clone();
+    for mut items in transpose("fold-right", &args[2..])?.into_iter().rev() {
+        items.push(acc);
+        acc = apply(&args[0], items, env)?;
+    }
+    Ok(acc)
+}
+
+fn builtin_reduce(args: &[Value], env: &Environment) -> Result<Value, EvalError> {
+    match args {
+        [_, _, _] => builtin_fold_left(args, env),
+        // Without an initial value, the first element starts things off
+        [f, list] => match expect_list("reduce", list)? {
+            [] => Err(ErrorKind::TypeError("reduce of an empty list requires an initial value".to_string()).into()),
+            [first, rest @ ..] => builtin_fold_left(&[f.clone(), first.clone(), Value::List(rest.to_vec())], env),
+        },
+        _ => Err(ErrorKind::ArityError("reduce requires 2 or 3 arguments (function, optional initial value, list)".to_string()).into()),
+    }
+}
+
+fn builtin_append(args: &[Value]) -> Result<Value, EvalError> {
+    let mut result = Vec::new();
     for arg in args {
-        if is_truthy(arg) {
-            return Ok(Value::Boolean(true));
+        result.extend(expect_list("append", arg)?.iter().cloned());
+    }
+    Ok(Value::List(result))
+}
+
+fn builtin_apply(args: &[Value], env: &Environment) -> Result<Value, EvalError> {
+    if args.len() < 2 {
+        return Err(ErrorKind::ArityError("apply requires a function and a list of arguments".to_string()).into());
+    }
+    
+    // Arguments between the function and the final list are passed first
+    let (spread, fixed) = args[1..].split_last().unwrap_or((&args[1], &[]));
+    let mut call_args = fixed.to_vec();
+    call_args.extend(expect_list("apply", spread)?.iter().cloned());
+    apply(&args[0], call_args, env)
+}
+
+fn builtin_sort(args: &[Value], env: &Environment) -> Result<Value, EvalError> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(ErrorKind::ArityError("sort requires 1 or 2 arguments (list, optional comparator)".to_string()).into());
+    }
+    
+    let items = expect_list("sort", &args[0])?.to_vec();
+    let mut less = |a: &Value, b: &Value| match args.get(1) {
+        Some(comparator) => Ok(is_truthy(&apply(comparator, vec![a.clone(), b.clone()], env)?)),
+        None => Ok(compare_values("sort", a, b)? == Some(Ordering::Less)),
+    };
+    Ok(Value::List(merge_sort(items, &mut less)?))
+}
+
+/// A stable sort that stops at the first error from `less`. The standard
+/// library's sorts can't propagate errors, and may panic if a user-supplied
+/// comparator isn't a consistent ordering.
+fn merge_sort(
+    mut items: Vec<Value>,
+    less: &mut dyn FnMut(&Value, &Value) -> Result<bool, EvalError>,
+) -> Result<Vec<Value>, EvalError> {
+    if items.len() < 2 {
+        return Ok(items);
+    }
+    
+    let right = merge_sort(items.split_off(items.len() / 2), less)?;
+    let left = merge_sort(items, less)?;
+    
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+    while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+        // Taking from the left on ties keeps equal elements in order
+        if less(b, a)? {
+            merged.extend(right.next());
+        } else {
+            merged.extend(left.next());
         }
     }
-    // All arguments are falsy
-    Ok(Value::Boolean(false))
+    merged.extend(left);
+    merged.extend(right);
+    Ok(merged)
 }
 
-pub fn eval_expr(expr: &Expr, env: &mut Environment) -> Result<Value, EvalError> {
-    match expr {
-        Expr::Number(n) => Ok(Value::Number(*n)),
-        Expr::String(s) => Ok(Value::String(s.clone())),
-        Expr::Symbol(s) => {
-            match s.as_str() {
-                "true" => Ok(Value::Boolean(true)),
-                "false" => Ok(Value::Boolean(false)),
-                _ => env.lookup(s)
-                    .cloned()
-                    .ok_or_else(|| EvalError::UndefinedSymbol(s.clone()))
+fn builtin_member(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(ErrorKind::ArityError("member requires exactly 2 arguments".to_string()).into());
+    }
+    
+    // The tail of the list starting at the first match
+    let list = expect_list("member", &args[1])?;
+    match list.iter().position(|item| *item == args[0]) {
+        Some(i) => Ok(Value::List(list[i..].to_vec())),
+        None => Ok(Value::Nil),
+    }
+}
+
+/// `(assoc key alist)` finds the first pair in an association list whose car
+/// is `key`, or nil.
+fn assoc_list(key: &Value, alist: &Value) -> Result<Value, EvalError> {
+    for pair in expect_list("assoc", alist)? {
+        match pair {
+            Value::List(elements) | Value::Vector(elements) if !elements.is_empty() => {
+                if elements[0] == *key {
+                    return Ok(pair.clone());
+                }
+            }
+            other => return Err(ErrorKind::TypeError(format!("assoc requires a list of pairs, got {}", other)).into()),
+        }
+    }
+    Ok(Value::Nil)
+}
+
+fn builtin_range(args: &[Value]) -> Result<Value, EvalError> {
+    let numbers = args
+        .iter()
+        .map(|arg| match arg {
+            Value::Number(n) => Ok(n.clone()),
+            other => Err(ErrorKind::TypeError(format!("range requires numbers, got {}", other.type_name())).into()),
+        })
+        .collect::<Result<Vec<_>, EvalError>>()?;
+    
+    let (start, end, step) = match numbers.as_slice() {
+        [end] => (Number::from(0), end.clone(), Number::from(1)),
+        [start, end] => (start.clone(), end.clone(), Number::from(1)),
+        [start, end, step] => (start.clone(), end.clone(), step.clone()),
+        _ => return Err(ErrorKind::ArityError("range requires 1 to 3 arguments (optional start, end, optional step)".to_string()).into()),
+    };
+    if step.is_zero() {
+        return Err(ErrorKind::TypeError("range requires a non-zero step".to_string()).into());
+    }
+    
+    // Counts up to, or down to, but not including `end`
+    let mut result = Vec::new();
+    let mut current = start;
+    while if step.is_negative() { current > end } else { current < end } {
+        let next = &current + &step;
+        result.push(Value::Number(current));
+        current = next;
+    }
+    Ok(Value::List(result))
+}
+
+fn builtin_take(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(ErrorKind::ArityError("take requires exactly 2 arguments".to_string()).into());
+    }
+    
+    let count = expect_position("take", &args[0])?;
+    let list = expect_list("take", &args[1])?;
+    Ok(Value::List(list.iter().take(count).cloned().collect()))
+}
+
+fn builtin_drop(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(ErrorKind::ArityError("drop requires exactly 2 arguments".to_string()).into());
+    }
+    
+    let count = expect_position("drop", &args[0])?;
+    let list = expect_list("drop", &args[1])?;
+    Ok(Value::List(list.iter().skip(count).cloned().collect()))
+}
+
+fn builtin_zip(args: &[Value]) -> Result<Value, EvalError> {
+    let tuples = transpose("zip", args)?;
+    Ok(Value::List(tuples.into_iter().map(Value::List).collect()))
+}
+
+/// Identity rather than structural equality: like `=`, except that numbers
+/// must also agree in exactness, and functions, lambdas and macros are only
+/// `eq?` to themselves.
+fn builtin_eq(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(ErrorKind::ArityError("eq? requires exactly 2 arguments".to_string()).into());
+    }
+    
+    let result = match (&args[0], &args[1]) {
+        (Value::Number(a), Value::Number(b)) => a.is_exact() == b.is_exact() && a == b,
+        (Value::Function(a), Value::Function(b)) => std::ptr::fn_addr_eq(*a, *b),
+        (Value::EnvFunction(a), Value::EnvFunction(b)) => std::ptr::fn_addr_eq(*a, *b),
+        (
+            Value::Lambda { body: a, closure: a_env, .. },
+            Value::Lambda { body: b, closure: b_env, .. },
+        )
+        | (
+            Value::Macro { body: a, closure: a_env, .. },
+            Value::Macro { body: b, closure: b_env, .. },
+        ) => Rc::ptr_eq(a, b) && Rc::ptr_eq(&a_env.frame, &b_env.frame),
+        (a, b) => a == b,
+    };
+    
+    Ok(Value::Boolean(result))
+}
+
+fn builtin_is_number(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("number? requires exactly 1 argument".to_string()).into());
+    }
+    
+    Ok(Value::Boolean(matches!(&args[0], Value::Number(_))))
+}
+
+fn builtin_is_integer(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("integer? requires exactly 1 argument".to_string()).into());
+    }
+    
+    Ok(Value::Boolean(matches!(&args[0], Value::Number(n) if n.is_integer())))
+}
+
+fn builtin_is_exact(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("exact? requires exactly 1 argument".to_string()).into());
+    }
+    
+    Ok(Value::Boolean(matches!(&args[0], Value::Number(n) if n.is_exact())))
+}
+
+fn builtin_is_string(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("string? requires exactly 1 argument".to_string()).into());
+    }
+    
+    Ok(Value::Boolean(matches!(&args[0], Value::String(_))))
+}
+
+fn builtin_is_char(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("char? requires exactly 1 argument".to_string()).into());
+    }
+    
+    Ok(Value::Boolean(matches!(&args[0], Value::Char(_))))
+}
+
+fn builtin_is_symbol(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("symbol? requires exactly 1 argument".to_string()).into());
+    }
+    
+    Ok(Value::Boolean(matches!(&args[0], Value::Symbol(_))))
+}
+
+fn builtin_is_boolean(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("boolean? requires exactly 1 argument".to_string()).into());
+    }
+    
+    Ok(Value::Boolean(matches!(&args[0], Value::Boolean(_))))
+}
+
+fn builtin_is_list(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("list? requires exactly 1 argument".to_string()).into());
+    }
+    
+    Ok(Value::Boolean(matches!(&args[0], Value::List(_))))
+}
+
+fn builtin_is_vector(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("vector? requires exactly 1 argument".to_string()).into());
+    }
+    
+    Ok(Value::Boolean(matches!(&args[0], Value::Vector(_))))
+}
+
+fn builtin_is_map(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("map? requires exactly 1 argument".to_string()).into());
+    }
+    
+    Ok(Value::Boolean(matches!(&args[0], Value::Map(_))))
+}
+
+fn builtin_is_set(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("set? requires exactly 1 argument".to_string()).into());
+    }
+    
+    Ok(Value::Boolean(matches!(&args[0], Value::Set(_))))
+}
+
+fn builtin_is_nil(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("nil? requires exactly 1 argument".to_string()).into());
+    }
+    
+    Ok(Value::Boolean(matches!(&args[0], Value::Nil)))
+}
+
+fn builtin_is_function(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("function? requires exactly 1 argument".to_string()).into());
+    }
+    
+    Ok(Value::Boolean(matches!(&args[0], Value::Function(_) | Value::EnvFunction(_) | Value::Lambda { .. })))
+}
+
+/// Calls a keyword as a function: `(:name person)` looks the keyword up in
+/// `person`, like `(get person :name)`, with an optional default.
+fn keyword_lookup(keyword: &Keyword, args: &[Value]) -> Result<Value, EvalError> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(ErrorKind::ArityError(format!("{} requires 1 or 2 arguments (map, optional default)", keyword)).into());
+    }
+    
+    let mut get_args = vec![args[0].clone(), Value::Keyword(keyword.clone())];
+    get_args.extend(args.get(1).cloned());
+    builtin_get(&get_args)
+}
+
+fn builtin_keyword(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("keyword requires exactly 1 argument".to_string()).into());
+    }
+    
+    let name = match &args[0] {
+        Value::Keyword(k) => return Ok(Value::Keyword(k.clone())),
+        Value::String(s) | Value::Symbol(s) => s,
+        other => return Err(ErrorKind::TypeError(format!("keyword requires a string or symbol, got {}", other.type_name())).into()),
+    };
+    // Names that couldn't be written as a keyword literal wouldn't print
+    // readably
+    if !tokenizer::is_keyword_name(name) {
+        return Err(ErrorKind::TypeError(format!("keyword requires a name made of symbol characters, got {:?}", name)).into());
+    }
+    
+    Ok(Value::Keyword(Keyword::new(name)))
+}
+
+fn builtin_is_keyword(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("keyword? requires exactly 1 argument".to_string()).into());
+    }
+    
+    Ok(Value::Boolean(matches!(args[0], Value::Keyword(_))))
+}
+
+fn builtin_name(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("name requires exactly 1 argument".to_string()).into());
+    }
+    
+    match &args[0] {
+        Value::Keyword(k) => Ok(Value::String(k.name().to_string())),
+        Value::Symbol(s) | Value::String(s) => Ok(Value::String(s.clone())),
+        other => Err(ErrorKind::TypeError(format!("name requires a keyword, symbol or string, got {}", other.type_name())).into()),
+    }
+}
+
+fn builtin_update(args: &[Value], env: &Environment) -> Result<Value, EvalError> {
+    if args.len() < 3 {
+        return Err(ErrorKind::ArityError("update requires at least 3 arguments (collection, key, function)".to_string()).into());
+    }
+    
+    // (update coll key f a b) => (assoc coll key (f (get coll key) a b))
+    let old = builtin_get(&args[..2])?;
+    let mut call_args = vec![old];
+    call_args.extend(args[3..].iter().cloned());
+    let new = apply(&args[2], call_args, env)?;
+    
+    builtin_assoc(&[args[0].clone(), args[1].clone(), new])
+}
+
+fn builtin_macroexpand_1(args: &[Value], env: &Environment) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("macroexpand-1 requires exactly 1 argument".to_string()).into());
+    }
+    
+    Ok(macroexpand_1(&args[0], env)?.unwrap_or_else(|| args[0].clone()))
+}
+
+fn builtin_macroexpand(args: &[Value], env: &Environment) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("macroexpand requires exactly 1 argument".to_string()).into());
+    }
+    
+    let mut form = args[0].clone();
+    while let Some(expanded) = macroexpand_1(&form, env)? {
+        form = expanded;
+    }
+    Ok(form)
+}
+
+/// Expands `form` once if it is a call to a macro bound in `env`, returning
+/// `None` if it isn't a macro call.
+fn macroexpand_1(form: &Value, env: &Environment) -> Result<Option<Value>, EvalError> {
+    if let Value::List(elements) = form
+        && let Some(Value::Symbol(name)) = elements.first()
+        && let Some(Value::Macro { params, body, closure }) = env.lookup(name)
+    {
+        return expand_macro(&params, &body, &closure, elements[1..].to_vec()).map(Some);
+    }
+    Ok(None)
+}
+
+/// Runs a macro body with its parameters bound to the unevaluated argument
+/// forms, producing the expansion as data.
+fn expand_macro(params: &Params, body: &[Expr], closure: &Environment, args: Vec<Value>) -> Result<Value, EvalError> {
+    let frame = bind_arguments(params, args, closure)?;
+    eval_sequence(body, &frame)
+}
+
+fn builtin_throw(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("throw requires exactly 1 argument".to_string()).into());
+    }
+    
+    // Rethrowing a caught builtin error raises it again with its original kind
+    match &args[0] {
+        Value::Error(kind) => Err((**kind).clone().into()),
+        value => Err(ErrorKind::Thrown(Box::new(value.clone())).into()),
+    }
+}
+
+fn builtin_is_error(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("error? requires exactly 1 argument".to_string()).into());
+    }
+    
+    Ok(Value::Boolean(matches!(args[0], Value::Error(_))))
+}
+
+fn builtin_error_kind(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("error-kind requires exactly 1 argument".to_string()).into());
+    }
+    
+    match &args[0] {
+        Value::Error(kind) => Ok(Value::Symbol(kind.name().to_string())),
+        _ => Err(ErrorKind::TypeError("error-kind requires an error".to_string()).into()),
+    }
+}
+
+fn builtin_error_message(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("error-message requires exactly 1 argument".to_string()).into());
+    }
+    
+    match &args[0] {
+        Value::Error(kind) => Ok(Value::String(kind.to_string())),
+        _ => Err(ErrorKind::TypeError("error-message requires an error".to_string()).into()),
+    }
+}
+
+thread_local! {
+    /// Input `read` has taken from stdin but not used yet, since a line may
+    /// hold more than one datum
+    static STDIN_PENDING: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+/// The outcome of reading the first datum in some text.
+enum Read {
+    /// The datum, and the byte offset just past it
+    Datum(Value, usize),
+    /// The text holds nothing but whitespace and comments
+    Empty,
+    /// The text ends partway through a datum
+    Incomplete(EvalError),
+}
+
+fn read_datum(source: &str) -> Result<Read, EvalError> {
+    let tokens = match tokenizer::tokenize(source) {
+        Ok(tokens) => tokens,
+        Err(err) if err.is_incomplete() => return Ok(Read::Incomplete(ErrorKind::ReadError(err.to_string()).into())),
+        Err(err) => return Err(ErrorKind::ReadError(err.to_string()).into()),
+    };
+    
+    match parser::parse(tokens) {
+        Ok(exprs) => match exprs.first() {
+            Some(expr) => Ok(Read::Datum(Value::from_expr(expr), expr.span.end)),
+            None => Ok(Read::Empty),
+        },
+        Err(err) if err.is_incomplete() => Ok(Read::Incomplete(ErrorKind::ReadError(err.to_string()).into())),
+        Err(err) => Err(ErrorKind::ReadError(err.to_string()).into()),
+    }
+}
+
+/// Reads the next datum from stdin, without evaluating it. Returns nil at
+/// the end of input.
+fn builtin_read(args: &[Value]) -> Result<Value, EvalError> {
+    if !args.is_empty() {
+        return Err(ErrorKind::ArityError("read takes no arguments".to_string()).into());
+    }
+    
+    STDIN_PENDING.with(|pending| {
+        let mut pending = pending.borrow_mut();
+        loop {
+            let incomplete = match read_datum(&pending) {
+                Ok(Read::Datum(value, end)) => {
+                    pending.drain(..end);
+                    return Ok(value);
+                }
+                Ok(Read::Empty) => {
+                    pending.clear();
+                    None
+                }
+                Ok(Read::Incomplete(err)) => Some(err),
+                // Discard the bad input, so the next read starts afresh
+                Err(err) => {
+                    pending.clear();
+                    return Err(err);
+                }
+            };
+            
+            let mut line = String::new();
+            let count = io::stdin()
+                .read_line(&mut line)
+                .map_err(|err| EvalError::from(ErrorKind::ReadError(format!("cannot read stdin: {}", err))))?;
+            if count == 0 {
+                pending.clear();
+                return match incomplete {
+                    Some(err) => Err(err),
+                    None => Ok(Value::Nil),
+                };
+            }
+            pending.push_str(&line);
+        }
+    })
+}
+
+/// Reads the first datum in a string, without evaluating it. Returns nil if
+/// the string holds none.
+fn builtin_read_string(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("read-string requires exactly 1 argument".to_string()).into());
+    }
+    
+    let source = expect_string("read-string", &args[0])?;
+    match read_datum(source)? {
+        Read::Datum(value, _) => Ok(value),
+        Read::Empty => Ok(Value::Nil),
+        Read::Incomplete(err) => Err(err),
+    }
+}
+
+fn builtin_eval(args: &[Value], env: &Environment) -> Result<Value, EvalError> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(ErrorKind::ArityError("eval requires 1 or 2 arguments (expression, optional environment)".to_string()).into());
+    }
+    
+    let target = match args.get(1) {
+        None => env,
+        Some(Value::Environment(target)) => target,
+        Some(_) => return Err(ErrorKind::TypeError("eval requires an environment as second argument".to_string()).into()),
+    };
+    
+    let expr = args[0].to_expr()?;
+    eval_expr(&expr, target).map_err(|mut err| {
+        // Code built from data has no place in any source, so an error
+        // located there is reported at the call to eval instead. Errors
+        // from functions the code called keep their own locations.
+        if err.span == Some(Span::default()) {
+            err.span = None;
+        }
+        err
+    })
+}
+
+fn builtin_load(args: &[Value], env: &Environment) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("load requires exactly 1 argument".to_string()).into());
+    }
+    
+    module::load(expect_string("load", &args[0])?, env)
+}
+
+fn builtin_current_environment(args: &[Value], env: &Environment) -> Result<Value, EvalError> {
+    if !args.is_empty() {
+        return Err(ErrorKind::ArityError("current-environment takes no arguments".to_string()).into());
+    }
+    
+    Ok(Value::Environment(env.clone()))
+}
+
+/// Returns a fresh global environment, holding only the builtins.
+fn builtin_make_environment(args: &[Value], env: &Environment) -> Result<Value, EvalError> {
+    if !args.is_empty() {
+        return Err(ErrorKind::ArityError("make-environment takes no arguments".to_string()).into());
+    }
+    
+    Ok(Value::Environment(env.new_namespace()))
+}
+
+/// The result of evaluating a form that may end in a tail position: either a
+/// finished value, or an expression still to be evaluated in some environment.
+/// `eval_expr` loops on `Eval` instead of recursing, so tail calls run in
+/// constant Rust stack space.
+enum Tail {
+    Value(Value),
+    Eval(Expr, Environment),
+    /// Like `Eval`, but entering the body of a procedure call. The call
+    /// replaces the current activation, so tail calls don't grow backtraces.
+    Call(Expr, Environment, CallFrame),
+}
+
+/// How deeply `eval_expr` may nest before evaluation fails with a
+/// `RecursionError`, rather than overflowing the Rust stack.
+pub const MAX_DEPTH: usize = 10_000;
+
+/// The Rust stack evaluation needs to reach `MAX_DEPTH` even in debug builds.
+/// The interpreter runs on a thread of this size.
+pub const STACK_SIZE: usize = 512 * 1024 * 1024;
+
+thread_local! {
+    /// How many calls to `eval_expr` are in progress
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// One level of `eval_expr` nesting, given back when dropped.
+struct Depth;
+
+impl Depth {
+    fn enter() -> Result<Depth, EvalError> {
+        DEPTH.with(|depth| {
+            if depth.get() >= MAX_DEPTH {
+                return Err(ErrorKind::RecursionError.into());
+            }
+            depth.set(depth.get() + 1);
+            Ok(Depth)
+        })
+    }
+}
+
+impl Drop for Depth {
+    fn drop(&mut self) {
+        DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+pub fn eval_expr(expr: &Expr, env: &Environment) -> Result<Value, EvalError> {
+    let _depth = Depth::enter().map_err(|err| err.at(expr.span))?;
+    let mut expr = expr.clone();
+    let mut env = env.clone();
+    let mut call: Option<CallFrame> = None;
+    
+    loop {
+        let tail = match eval_step(&expr, &env) {
+            Ok(tail) => tail,
+            Err(err) => {
+                let err = err.at(expr.span);
+                return Err(match call {
+                    Some(call) => err.in_frame(call),
+                    None => err,
+                });
+            }
+        };
+        
+        match tail {
+            Tail::Value(value) => return Ok(value),
+            Tail::Eval(next_expr, next_env) => {
+                expr = next_expr;
+                env = next_env;
             }
+            Tail::Call(next_expr, next_env, next_call) => {
+                expr = next_expr;
+                env = next_env;
+                call = Some(next_call);
+            }
+        }
+    }
+}
+
+fn eval_step(expr: &Expr, env: &Environment) -> Result<Tail, EvalError> {
+    match &expr.kind {
+        ExprKind::Number(n) => Ok(Tail::Value(Value::Number(n.clone()))),
+        ExprKind::String(s) => Ok(Tail::Value(Value::String(s.clone()))),
+        ExprKind::Symbol(s) => {
+            let value = match s.as_str() {
+                "true" => Value::Boolean(true),
+                "false" => Value::Boolean(false),
+                "nil" => Value::Nil,
+                _ => env.lookup(s)
+                    .ok_or_else(|| ErrorKind::UndefinedSymbol(s.clone()))?
+            };
+            Ok(Tail::Value(value))
+        }
+        ExprKind::Vector(_) | ExprKind::Map(_) | ExprKind::Set(_) => eval_collection(expr, env).map(Tail::Value),
+        ExprKind::Keyword(k) => Ok(Tail::Value(Value::Keyword(k.clone()))),
+        ExprKind::Char(ch) => Ok(Tail::Value(Value::Char(*ch))),
+        ExprKind::Quote(expr) => Ok(Tail::Value(Value::from_expr(expr))),
+        ExprKind::Quasiquote(expr) => eval_quasiquote(expr, env, 1).map(Tail::Value),
+        ExprKind::Unquote(_) | ExprKind::UnquoteSplicing(_) => {
+            Err(ErrorKind::TypeError("unquote used outside of quasiquote".to_string()).into())
         }
-        Expr::Quote(expr) => eval_quote(expr),
-        Expr::List(elements) => {
+        ExprKind::List(elements) => {
             if elements.is_empty() {
-                Ok(Value::List(vec![]))
-            } else {
-                // Check for special forms
-                if let Expr::Symbol(name) = &elements[0] {
-                    match name.as_str() {
-                        "def" => eval_def(&elements[1..], env),
-                        "defn" => eval_defn(&elements[1..], env),
-                        "lambda" => eval_lambda(&elements[1..], env),
-                        "do" => eval_do(&elements[1..], env),
-                        "if" => eval_if(&elements[1..], env),
-                        "cond" => eval_cond(&elements[1..], env),
-                        _ => eval_function_call(elements, env),
-                    }
-                } else {
-                    eval_function_call(elements, env)
+                return Ok(Tail::Value(Value::List(vec![])));
+            }
+            
+            // Check for special forms. Results are passed straight through,
+            // since every temporary here adds to the Rust stack each level of
+            // a recursive program uses
+            if let ExprKind::Symbol(name) = &elements[0].kind {
+                match name.as_str() {
+                    "quote" => eval_quote_form(&elements[1..]).map(Tail::Value),
+                    "def" => eval_def(&elements[1..], env).map(Tail::Value),
+                    "set!" => eval_set(&elements[1..], env).map(Tail::Value),
+                    "defn" => eval_defn(&elements[1..], env).map(Tail::Value),
+                    "defmacro" => eval_defmacro(&elements[1..], env).map(Tail::Value),
+                    "lambda" => eval_lambda(&elements[1..], env).map(Tail::Value),
+                    "do" => eval_do(&elements[1..], env),
+                    "if" => eval_if(&elements[1..], env),
+                    "cond" => eval_cond(&elements[1..], env),
+                    "and" => eval_and(&elements[1..], env),
+                    "or" => eval_or(&elements[1..], env),
+                    "let" => eval_let(&elements[1..], env),
+                    "let*" => eval_let_star(&elements[1..], env),
+                    "letrec" => eval_letrec(&elements[1..], env),
+                    "try" => eval_try(&elements[1..], env).map(Tail::Value),
+                    "require" => eval_require(&elements[1..], env).map(Tail::Value),
+                    "provide" => eval_provide(&elements[1..], env).map(Tail::Value),
+                    _ => eval_function_call(expr, elements, env),
                 }
+            } else {
+                eval_function_call(expr, elements, env)
+            }
+        }
+    }
+}
+
+fn quoted_form(name: &str, value: Value) -> Value {
+    Value::List(vec![Value::Symbol(name.to_string()), value])
+}
+
+/// Evaluates the elements of a vector, map or set literal.
+fn eval_collection(expr: &Expr, env: &Environment) -> Result<Value, EvalError> {
+    match &expr.kind {
+        ExprKind::Vector(elements) => {
+            let values = elements.iter().map(|elem| eval_expr(elem, env)).collect::<Result<_, _>>()?;
+            Ok(Value::Vector(values))
+        }
+        ExprKind::Map(elements) => {
+            let mut map = Map::new();
+            for pair in elements.chunks(2) {
+                map.insert(eval_expr(&pair[0], env)?, eval_expr(&pair[1], env)?);
             }
+            Ok(Value::Map(Box::new(map)))
         }
+        ExprKind::Set(elements) => {
+            let values = elements.iter().map(|elem| eval_expr(elem, env)).collect::<Result<_, _>>()?;
+            Ok(Value::Set(Box::new(values)))
+        }
+        _ => Ok(Value::from_expr(expr)),
+    }
+}
+
+fn eval_quote_form(args: &[Expr]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(ErrorKind::ArityError("quote requires exactly 1 argument".to_string()).into());
     }
+    
+    Ok(Value::from_expr(&args[0]))
 }
 
-fn eval_quote(expr: &Expr) -> Result<Value, EvalError> {
-    match expr {
-        Expr::Number(n) => Ok(Value::Number(*n)),
-        Expr::String(s) => Ok(Value::String(s.clone())),
-        Expr::Symbol(s) => {
-            match s.as_str() {
-                "true" => Ok(Value::Boolean(true)),
-                "false" => Ok(Value::Boolean(false)),
-                _ => Ok(Value::Symbol(s.clone()))
+/// Evaluates a quasiquoted template. `depth` tracks nesting of quasiquotes, so
+/// only unquotes belonging to the outermost one are evaluated.
+fn eval_quasiquote(expr: &Expr, env: &Environment, depth: usize) -> Result<Value, EvalError> {
+    match &expr.kind {
+        ExprKind::Unquote(inner) => {
+            if depth == 1 {
+                eval_expr(inner, env)
+            } else {
+                Ok(quoted_form("unquote", eval_quasiquote(inner, env, depth - 1)?))
             }
         }
-        Expr::List(elements) => {
-            let mut values = Vec::new();
-            for elem in elements {
-                values.push(eval_quote(elem)?);
+        ExprKind::UnquoteSplicing(inner) => {
+            if depth == 1 {
+                Err(ErrorKind::TypeError("unquote-splicing must appear inside a list".to_string()).into())
+            } else {
+                Ok(quoted_form("unquote-splicing", eval_quasiquote(inner, env, depth - 1)?))
             }
-            Ok(Value::List(values))
         }
-        Expr::Quote(expr) => eval_quote(expr),
+        ExprKind::Quasiquote(inner) => Ok(quoted_form("quasiquote", eval_quasiquote(inner, env, depth + 1)?)),
+        ExprKind::Quote(inner) => Ok(quoted_form("quote", eval_quasiquote(inner, env, depth)?)),
+        ExprKind::List(elements) => Ok(Value::List(eval_quasiquote_elements(elements, env, depth)?)),
+        ExprKind::Vector(elements) => Ok(Value::Vector(eval_quasiquote_elements(elements, env, depth)?)),
+        ExprKind::Set(elements) => Ok(Value::Set(Box::new(eval_quasiquote_elements(elements, env, depth)?.into_iter().collect()))),
+        ExprKind::Map(elements) => {
+            let mut map = Map::new();
+            for pair in elements.chunks(2) {
+                map.insert(eval_quasiquote(&pair[0], env, depth)?, eval_quasiquote(&pair[1], env, depth)?);
+            }
+            Ok(Value::Map(Box::new(map)))
+        }
+        _ => Ok(Value::from_expr(expr)),
+    }
+}
+
+/// Quasiquotes the elements of a list, vector or set, splicing in the items
+/// of any `,@` forms.
+fn eval_quasiquote_elements(elements: &[Expr], env: &Environment, depth: usize) -> Result<Vec<Value>, EvalError> {
+    let mut values = Vec::new();
+    for elem in elements {
+        match &elem.kind {
+            ExprKind::UnquoteSplicing(inner) if depth == 1 => match eval_expr(inner, env)? {
+                Value::List(items) | Value::Vector(items) => values.extend(items),
+                Value::Nil => {}
+                other => return Err(ErrorKind::TypeError(format!("unquote-splicing requires a list, got {}", other)).into()),
+            },
+            _ => values.push(eval_quasiquote(elem, env, depth)?),
+        }
     }
+    Ok(values)
+}
+
+fn eval_def(args: &[Expr], env: &Environment) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(ErrorKind::ArityError("def requires exactly 2 arguments".to_string()).into());
+    }
+    
+    let name = match &args[0].kind {
+        ExprKind::Symbol(s) => s.clone(),
+        _ => return Err(ErrorKind::TypeError("def requires a symbol as first argument".to_string()).into()),
+    };
+    
+    // Anonymous lambdas take the name they are first defined under, so they
+    // show up by name in backtraces
+    let value = match eval_expr(&args[1], env)? {
+        Value::Lambda { name: None, params, body, closure } => Value::Lambda {
+            name: Some(name.as_str().into()),
+            params,
+            body,
+            closure,
+        },
+        value => value,
+    };
+    env.global().define(&name, value.clone());
+    Ok(value)
 }
 
-fn eval_def(args: &[Expr], env: &mut Environment) -> Result<Value, EvalError> {
+fn eval_set(args: &[Expr], env: &Environment) -> Result<Value, EvalError> {
     if args.len() != 2 {
-        return Err(EvalError::ArityError("def requires exactly 2 arguments".to_string()));
+        return Err(ErrorKind::ArityError("set! requires exactly 2 arguments".to_string()).into());
     }
     
-    let name = match &args[0] {
-        Expr::Symbol(s) => s.clone(),
-        _ => return Err(EvalError::TypeError("def requires a symbol as first argument".to_string())),
+    let name = match &args[0].kind {
+        ExprKind::Symbol(s) => s,
+        _ => return Err(ErrorKind::TypeError("set! requires a symbol as first argument".to_string()).into()),
     };
     
     let value = eval_expr(&args[1], env)?;
-    env.define(&name, value.clone());
+    if !env.set(name, value.clone()) {
+        return Err(EvalError::from(ErrorKind::UndefinedSymbol(name.clone())).at(args[0].span));
+    }
     Ok(value)
 }
 
-fn eval_defn(args: &[Expr], env: &mut Environment) -> Result<Value, EvalError> {
-    if args.len() < 3 {
-        return Err(EvalError::ArityError("defn requires at least 3 arguments".to_string()));
-    }
+/// `(require "lib/math")` defines everything the module exports;
+/// `(require "lib/math" :as m)` defines them qualified instead, as `m/square`.
+fn eval_require(args: &[Expr], env: &Environment) -> Result<Value, EvalError> {
+    let prefix = match args {
+        [_] => None,
+        [_, option, alias] => match (&option.kind, &alias.kind) {
+            (ExprKind::Keyword(k), ExprKind::Symbol(alias)) if k.name() == "as" => Some(alias),
+            _ => return Err(ErrorKind::TypeError("require expects a module name, optionally followed by :as and a symbol".to_string()).into()),
+        },
+        _ => return Err(ErrorKind::ArityError("require requires a module name, optionally followed by :as and a symbol".to_string()).into()),
+    };
     
-    let name = match &args[0] {
-        Expr::Symbol(s) => s.clone(),
-        _ => return Err(EvalError::TypeError("defn requires a symbol as first argument".to_string())),
+    let name = match eval_expr(&args[0], env)? {
+        Value::String(name) => name,
+        other => return Err(ErrorKind::TypeError(format!("require requires a string module name, got {}", other.type_name())).into()),
     };
     
-    let params = match &args[1] {
-        Expr::List(param_exprs) => {
-            let mut params = Vec::new();
-            for param_expr in param_exprs {
-                match param_expr {
-                    Expr::Symbol(s) => params.push(s.clone()),
-                    _ => return Err(EvalError::TypeError("defn parameters must be symbols".to_string())),
-                }
-            }
-            params
+    let exports = module::require(&name, env).map_err(|err| err.called_from(args[0].span))?;
+    for (export, value) in exports.iter() {
+        match prefix {
+            Some(prefix) => env.define(&format!("{}/{}", prefix, export), value.clone()),
+            None => env.define(export, value.clone()),
+        }
+    }
+    Ok(Value::Nil)
+}
+
+fn eval_provide(args: &[Expr], env: &Environment) -> Result<Value, EvalError> {
+    let mut names = Vec::new();
+    for arg in args {
+        match &arg.kind {
+            ExprKind::Symbol(name) => names.push(name.clone()),
+            _ => return Err(ErrorKind::TypeError("provide requires symbols".to_string()).into()),
         }
-        _ => return Err(EvalError::TypeError("defn requires a parameter list as second argument".to_string())),
+    }
+    
+    env.modules().borrow_mut().provide(names)?;
+    Ok(Value::Nil)
+}
+
+fn eval_defn(args: &[Expr], env: &Environment) -> Result<Value, EvalError> {
+    if args.len() < 3 {
+        return Err(ErrorKind::ArityError("defn requires at least 3 arguments".to_string()).into());
+    }
+    
+    let name = match &args[0].kind {
+        ExprKind::Symbol(s) => s.clone(),
+        _ => return Err(ErrorKind::TypeError("defn requires a symbol as first argument".to_string()).into()),
     };
     
-    let body = args[2..].to_vec();
+    let params = parse_params("defn", &args[1])?;
+    
+    let body = args[2..].into();
     
     let lambda = Value::Lambda {
-        params,
+        name: Some(name.as_str().into()),
+        params: Rc::new(params),
         body,
         closure: env.clone(),
     };
     
     // Define function in environment
-    env.define(&name, lambda.clone());
+    env.global().define(&name, lambda.clone());
     Ok(lambda)
 }
 
-fn eval_lambda(args: &[Expr], env: &mut Environment) -> Result<Value, EvalError> {
-    if args.len() < 2 {
-        return Err(EvalError::ArityError("lambda requires at least 2 arguments".to_string()));
+fn eval_defmacro(args: &[Expr], env: &Environment) -> Result<Value, EvalError> {
+    if args.len() < 3 {
+        return Err(ErrorKind::ArityError("defmacro requires at least 3 arguments".to_string()).into());
+    }
+    
+    let name = match &args[0].kind {
+        ExprKind::Symbol(s) => s.clone(),
+        _ => return Err(ErrorKind::TypeError("defmacro requires a symbol as first argument".to_string()).into()),
+    };
+    
+    let params = parse_params("defmacro", &args[1])?;
+    
+    let body = args[2..].into();
+    
+    let mac = Value::Macro {
+        params: Rc::new(params),
+        body,
+        closure: env.clone(),
+    };
+    
+    env.global().define(&name, mac.clone());
+    Ok(mac)
+}
+
+fn parse_params(form: &str, expr: &Expr) -> Result<Params, EvalError> {
+    let param_exprs = match &expr.kind {
+        ExprKind::List(param_exprs) => param_exprs,
+        // (lambda args ...) collects every argument into `args`
+        ExprKind::Symbol(s) => return Ok(Params { rest: Some(s.clone()), ..Params::default() }),
+        _ => return Err(ErrorKind::TypeError(format!("{} requires a parameter list", form)).into()),
+    };
+    
+    enum Section {
+        Required,
+        Optional,
+        Rest,
     }
     
-    let params = match &args[0] {
-        Expr::List(param_exprs) => {
-            let mut params = Vec::new();
-            for param_expr in param_exprs {
-                match param_expr {
-                    Expr::Symbol(s) => params.push(s.clone()),
-                    _ => return Err(EvalError::TypeError("lambda parameters must be symbols".to_string())),
+    let mut params = Params::default();
+    let mut section = Section::Required;
+    let mut iter = param_exprs.iter();
+    
+    while let Some(param_expr) = iter.next() {
+        match (&param_expr.kind, &section) {
+            (ExprKind::Symbol(s), _) if s == "&optional" => section = Section::Optional,
+            (ExprKind::Symbol(s), _) if s == "&rest" || s == "." => section = Section::Rest,
+            (ExprKind::Symbol(s), Section::Required) => params.required.push(s.clone()),
+            (ExprKind::Symbol(s), Section::Optional) => params.optional.push((s.clone(), None)),
+            (ExprKind::List(pair), Section::Optional) if pair.len() == 2 => match &pair[0].kind {
+                ExprKind::Symbol(s) => params.optional.push((s.clone(), Some(pair[1].clone()))),
+                _ => return Err(ErrorKind::TypeError(format!("{} parameters must be symbols", form)).into()),
+            },
+            (ExprKind::Symbol(s), Section::Rest) => {
+                params.rest = Some(s.clone());
+                if iter.next().is_some() {
+                    return Err(ErrorKind::TypeError(format!("{} rest parameter must come last", form)).into());
                 }
             }
-            params
+            _ => return Err(ErrorKind::TypeError(format!("{} parameters must be symbols", form)).into()),
         }
-        _ => return Err(EvalError::TypeError("lambda requires a parameter list as first argument".to_string())),
-    };
+    }
+    
+    if matches!(section, Section::Rest) && params.rest.is_none() {
+        return Err(ErrorKind::TypeError(format!("{} requires a name after &rest", form)).into());
+    }
+    
+    Ok(params)
+}
+
+/// Binds call arguments to parameters in a fresh frame on top of `closure`.
+/// Defaults for missing optional parameters are evaluated in that frame, so
+/// they can refer to the parameters before them.
+fn bind_arguments(params: &Params, args: Vec<Value>, closure: &Environment) -> Result<Environment, EvalError> {
+    if !params.accepts(args.len()) {
+        return Err(ErrorKind::ArityError(format!(
+            "Function expects {}, got {}",
+            params.describe_arity(),
+            args.len()
+        )).into());
+    }
+    
+    let frame = closure.extend();
+    let mut args = args.into_iter();
+    
+    for param in &params.required {
+        frame.define(param, args.next().unwrap_or(Value::Nil));
+    }
+    
+    for (param, default) in &params.optional {
+        let value = match (args.next(), default) {
+            (Some(arg), _) => arg,
+            (None, Some(default)) => eval_expr(default, &frame)?,
+            (None, None) => Value::Nil,
+        };
+        frame.define(param, value);
+    }
+    
+    if let Some(rest) = &params.rest {
+        frame.define(rest, Value::List(args.collect()));
+    }
+    
+    Ok(frame)
+}
+
+fn eval_lambda(args: &[Expr], env: &Environment) -> Result<Value, EvalError> {
+    if args.len() < 2 {
+        return Err(ErrorKind::ArityError("lambda requires at least 2 arguments".to_string()).into());
+    }
+    
+    let params = parse_params("lambda", &args[0])?;
     
-    let body = args[1..].to_vec();
+    let body = args[1..].into();
     
     Ok(Value::Lambda {
-        params,
+        name: None,
+        params: Rc::new(params),
         body,
         closure: env.clone(),
     })
 }
 
-fn eval_do(args: &[Expr], env: &mut Environment) -> Result<Value, EvalError> {
-    let mut result = Value::Nil;
-    for expr in args {
-        result = eval_expr(expr, env)?;
+/// Evaluates all but the last expression of a body for effect, and hands the
+/// last one back as a tail.
+fn eval_body(body: &[Expr], env: &Environment) -> Result<Tail, EvalError> {
+    match body.split_last() {
+        Some((last, init)) => {
+            for expr in init {
+                eval_expr(expr, env)?;
+            }
+            Ok(Tail::Eval(last.clone(), env.clone()))
+        }
+        None => Ok(Tail::Value(Value::Nil)),
     }
-    Ok(result)
 }
 
-fn eval_if(args: &[Expr], env: &mut Environment) -> Result<Value, EvalError> {
+/// Evaluates a procedure body in `frame`, recording `call` on any error that
+/// escapes it.
+fn eval_call_body(body: &[Expr], frame: &Environment, call: CallFrame) -> Result<Tail, EvalError> {
+    match eval_body(body, frame) {
+        Ok(Tail::Eval(expr, env)) => Ok(Tail::Call(expr, env, call)),
+        Ok(tail) => Ok(tail),
+        Err(err) => Err(err.in_frame(call)),
+    }
+}
+
+fn eval_do(args: &[Expr], env: &Environment) -> Result<Tail, EvalError> {
+    eval_body(args, env)
+}
+
+fn eval_if(args: &[Expr], env: &Environment) -> Result<Tail, EvalError> {
     if args.len() < 2 || args.len() > 3 {
-        return Err(EvalError::ArityError("if requires 2 or 3 arguments (condition, then, optional else)".to_string()));
+        return Err(ErrorKind::ArityError("if requires 2 or 3 arguments (condition, then, optional else)".to_string()).into());
     }
     
     let condition = eval_expr(&args[0], env)?;
     
     if is_truthy(&condition) {
         // Evaluate then branch
-        eval_expr(&args[1], env)
+        Ok(Tail::Eval(args[1].clone(), env.clone()))
     } else if args.len() == 3 {
         // Evaluate else branch
-        eval_expr(&args[2], env)
+        Ok(Tail::Eval(args[2].clone(), env.clone()))
     } else {
         // No else branch, return nil
-        Ok(Value::Nil)
+        Ok(Tail::Value(Value::Nil))
+    }
+}
+
+/// Evaluates operands left to right until one is falsy, returning that
+/// operand's value, or the last one's if all are truthy. `(and)` is true.
+fn eval_and(args: &[Expr], env: &Environment) -> Result<Tail, EvalError> {
+    let Some((last, rest)) = args.split_last() else {
+        return Ok(Tail::Value(Value::Boolean(true)));
+    };
+    
+    for arg in rest {
+        let value = eval_expr(arg, env)?;
+        if !is_truthy(&value) {
+            return Ok(Tail::Value(value));
+        }
+    }
+    
+    Ok(Tail::Eval(last.clone(), env.clone()))
+}
+
+/// Evaluates operands left to right until one is truthy, returning that
+/// operand's value, or the last one's if all are falsy. `(or)` is false.
+fn eval_or(args: &[Expr], env: &Environment) -> Result<Tail, EvalError> {
+    let Some((last, rest)) = args.split_last() else {
+        return Ok(Tail::Value(Value::Boolean(false)));
+    };
+    
+    for arg in rest {
+        let value = eval_expr(arg, env)?;
+        if is_truthy(&value) {
+            return Ok(Tail::Value(value));
+        }
     }
+    
+    Ok(Tail::Eval(last.clone(), env.clone()))
 }
 
-fn eval_cond(args: &[Expr], env: &mut Environment) -> Result<Value, EvalError> {
+fn eval_cond(args: &[Expr], env: &Environment) -> Result<Tail, EvalError> {
     for clause in args {
-        match clause {
-            Expr::List(clause_elements) => {
+        match &clause.kind {
+            ExprKind::List(clause_elements) => {
                 if clause_elements.len() < 2 {
-                    return Err(EvalError::TypeError("cond clause must have at least 2 elements (condition and result)".to_string()));
+                    return Err(ErrorKind::TypeError("cond clause must have at least 2 elements (condition and result)".to_string()).into());
                 }
                 
                 let condition_expr = &clause_elements[0];
                 let result_exprs = &clause_elements[1..];
                 
                 // Check for 'else' clause (special symbol that's always true)
-                let is_else_clause = matches!(condition_expr, Expr::Symbol(s) if s == "else");
+                let is_else_clause = matches!(&condition_expr.kind, ExprKind::Symbol(s) if s == "else");
                 
                 let condition_result = if is_else_clause {
-                    Value::Number(1.0) // else is always true
+                    Value::Boolean(true) // else is always true
                 } else {
                     eval_expr(condition_expr, env)?
                 };
                 
                 if is_truthy(&condition_result) {
-                    // Execute all expressions in the clause, return the last result
-                    let mut result = Value::Nil;
-                    for expr in result_exprs {
-                        result = eval_expr(expr, env)?;
-                    }
-                    return Ok(result);
+                    // Execute all expressions in the clause, the last one in tail position
+                    return eval_body(result_exprs, env);
                 }
             }
             _ => {
-                return Err(EvalError::TypeError("cond clauses must be lists".to_string()));
+                return Err(ErrorKind::TypeError("cond clauses must be lists".to_string()).into());
             }
         }
     }
     
     // No clause matched, return nil
-    Ok(Value::Nil)
+    Ok(Tail::Value(Value::Nil))
+}
+
+/// Parses a `((name init) ...)` binding list for the `let` family of forms.
+fn parse_bindings(form: &str, expr: &Expr) -> Result<Vec<(String, Expr)>, EvalError> {
+    let binding_exprs = match &expr.kind {
+        ExprKind::List(binding_exprs) => binding_exprs,
+        _ => return Err(ErrorKind::TypeError(format!("{} requires a list of bindings", form)).into()),
+    };
+    
+    let mut bindings = Vec::new();
+    for binding in binding_exprs.iter() {
+        match &binding.kind {
+            ExprKind::List(pair) if pair.len() == 2 => match &pair[0].kind {
+                ExprKind::Symbol(name) => bindings.push((name.clone(), pair[1].clone())),
+                _ => return Err(ErrorKind::TypeError(format!("{} binding names must be symbols", form)).into()),
+            },
+            _ => return Err(ErrorKind::TypeError(format!("{} bindings must be (name value) pairs", form)).into()),
+        }
+    }
+    Ok(bindings)
+}
+
+fn eval_let(args: &[Expr], env: &Environment) -> Result<Tail, EvalError> {
+    // Named let: (let name ((var init) ...) body...) binds `name` to a
+    // procedure over the vars, visible only inside the body, and calls it.
+    if let Some(ExprKind::Symbol(name)) = args.first().map(|arg| &arg.kind) {
+        if args.len() < 3 {
+            return Err(ErrorKind::ArityError("named let requires a name, bindings and a body".to_string()).into());
+        }
+        
+        let bindings = parse_bindings("let", &args[1])?;
+        let mut values = Vec::new();
+        for (_, init) in &bindings {
+            values.push(eval_expr(init, env)?);
+        }
+        
+        let loop_env = env.extend();
+        let params = Rc::new(Params {
+            required: bindings.into_iter().map(|(param, _)| param).collect(),
+            ..Params::default()
+        });
+        let name: Rc<str> = name.as_str().into();
+        loop_env.define(&name, Value::Lambda {
+            name: Some(name.clone()),
+            params: params.clone(),
+            body: args[2..].into(),
+            closure: loop_env.clone(),
+        });
+        
+        let frame = bind_arguments(&params, values, &loop_env)?;
+        let call = CallFrame {
+            function: name,
+            call_site: args[0].span,
+        };
+        return eval_call_body(&args[2..], &frame, call);
+    }
+    
+    if args.len() < 2 {
+        return Err(ErrorKind::ArityError("let requires bindings and a body".to_string()).into());
+    }
+    
+    // All inits are evaluated in the enclosing environment before any are bound
+    let bindings = parse_bindings("let", &args[0])?;
+    let mut values = Vec::new();
+    for (_, init) in &bindings {
+        values.push(eval_expr(init, env)?);
+    }
+    
+    let frame = env.extend();
+    for ((name, _), value) in bindings.iter().zip(values) {
+        frame.define(name, value);
+    }
+    eval_body(&args[1..], &frame)
+}
+
+fn eval_let_star(args: &[Expr], env: &Environment) -> Result<Tail, EvalError> {
+    if args.len() < 2 {
+        return Err(ErrorKind::ArityError("let* requires bindings and a body".to_string()).into());
+    }
+    
+    // Each binding gets its own frame, so later inits see earlier names
+    let mut frame = env.clone();
+    for (name, init) in parse_bindings("let*", &args[0])? {
+        let value = eval_expr(&init, &frame)?;
+        frame = frame.extend();
+        frame.define(&name, value);
+    }
+    eval_body(&args[1..], &frame.extend())
+}
+
+fn eval_letrec(args: &[Expr], env: &Environment) -> Result<Tail, EvalError> {
+    if args.len() < 2 {
+        return Err(ErrorKind::ArityError("letrec requires bindings and a body".to_string()).into());
+    }
+    
+    // Inits are evaluated inside the new frame, so procedures defined here
+    // can refer to each other (and themselves)
+    let frame = env.extend();
+    for (name, init) in parse_bindings("letrec", &args[0])? {
+        let value = eval_expr(&init, &frame)?;
+        frame.define(&name, value);
+    }
+    eval_body(&args[1..], &frame)
+}
+
+/// A `(catch kind name body...)` clause of a `try` form.
+struct CatchClause<'a> {
+    kind: &'a str,
+    name: &'a str,
+    body: &'a [Expr],
+}
+
+/// `(try body... (catch kind name handler...)... (finally cleanup...))`
+///
+/// Errors raised in the body are matched against the catch clauses in order;
+/// `kind` is one of the names from `ErrorKind::name` (`user` for values
+/// raised with `throw`), or `any` to match everything. The handler runs with
+/// `name` bound to the thrown value, or to an error value for builtin errors.
+/// The finally clause runs however the body and handlers exit.
+fn eval_try(args: &[Expr], env: &Environment) -> Result<Value, EvalError> {
+    let clause_head = |expr: &Expr| match &expr.kind {
+        ExprKind::List(elements) => match elements.first().map(|head| &head.kind) {
+            Some(ExprKind::Symbol(s)) if s == "catch" || s == "finally" => Some(s.clone()),
+            _ => None,
+        },
+        _ => None,
+    };
+    
+    let body_len = args.iter().position(|arg| clause_head(arg).is_some()).unwrap_or(args.len());
+    let body = &args[..body_len];
+    
+    let mut handlers = Vec::new();
+    let mut finally: Option<&[Expr]> = None;
+    for clause in &args[body_len..] {
+        let elements = match &clause.kind {
+            ExprKind::List(elements) => elements,
+            _ => return Err(ErrorKind::TypeError("try body must come before catch and finally clauses".to_string()).into()),
+        };
+        if finally.is_some() {
+            return Err(ErrorKind::TypeError("finally must be the last clause of try".to_string()).into());
+        }
+        
+        match clause_head(clause).as_deref() {
+            Some("catch") => match (elements.get(1).map(|e| &e.kind), elements.get(2).map(|e| &e.kind)) {
+                // A misspelled kind would never match, letting the error
+                // through, so it is rejected instead
+                (Some(ExprKind::Symbol(kind)), Some(ExprKind::Symbol(_)))
+                    if kind != "any" && !ErrorKind::NAMES.contains(&kind.as_str()) =>
+                {
+                    return Err(ErrorKind::TypeError(format!("unknown error kind in catch: {}", kind)).into());
+                }
+                (Some(ExprKind::Symbol(kind)), Some(ExprKind::Symbol(name))) => handlers.push(CatchClause {
+                    kind,
+                    name,
+                    body: &elements[3..],
+                }),
+                _ => return Err(ErrorKind::TypeError("catch requires an error kind and a name".to_string()).into()),
+            },
+            Some(_) => finally = Some(&elements[1..]),
+            None => return Err(ErrorKind::TypeError("try body must come before catch and finally clauses".to_string()).into()),
+        }
+    }
+    
+    let mut result = eval_sequence(body, env);
+    
+    if let Err(err) = &result
+        && let Some(handler) = handlers.iter().find(|h| h.kind == "any" || h.kind == err.kind.name())
+    {
+        let caught = match &err.kind {
+            ErrorKind::Thrown(value) => (**value).clone(),
+            kind => Value::Error(Box::new(kind.clone())),
+        };
+        let frame = env.extend();
+        frame.define(handler.name, caught);
+        result = eval_sequence(handler.body, &frame);
+    }
+    
+    if let Some(cleanup) = finally {
+        eval_sequence(cleanup, env)?;
+    }
+    
+    result
+}
+
+/// Calls `func` on already evaluated arguments, for builtins that take functions.
+pub fn apply(func: &Value, args: Vec<Value>, env: &Environment) -> Result<Value, EvalError> {
+    match func {
+        Value::Function(f) => f(&args),
+        Value::EnvFunction(f) => f(&args, env),
+        Value::Lambda { name, params, body, closure } => {
+            let frame = bind_arguments(params, args, closure)?;
+            // Where the builtin calling this was itself called isn't known
+            // here; the frame is located once the error gets back to that call
+            let call = CallFrame {
+                function: name.clone().unwrap_or_else(|| "<lambda>".into()),
+                call_site: Span::default(),
+            };
+            eval_sequence(body, &frame).map_err(|err| err.in_frame(call))
+        }
+        Value::Keyword(keyword) => keyword_lookup(keyword, &args),
+        _ => Err(ErrorKind::InvalidFunction(format!("Not a function: {}", func)).into()),
+    }
+}
+
+/// Evaluates expressions in order, returning the value of the last one.
+fn eval_sequence(exprs: &[Expr], env: &Environment) -> Result<Value, EvalError> {
+    let mut result = Value::Nil;
+    for expr in exprs {
+        result = eval_expr(expr, env)?;
+    }
+    Ok(result)
 }
 
 fn is_truthy(value: &Value) -> bool {
     match value {
         Value::Nil => false,
         Value::Boolean(b) => *b,
-        Value::Number(n) => *n != 0.0,
+        Value::Number(n) => !n.is_zero(),
         Value::String(s) => !s.is_empty(),
-        Value::List(list) => !list.is_empty(),
+        Value::List(list) | Value::Vector(list) => !list.is_empty(),
+        Value::Map(map) => !map.is_empty(),
+        Value::Set(set) => !set.is_empty(),
         _ => true, // Functions, symbols, and other values are truthy
     }
 }
 
-fn eval_function_call(elements: &[Expr], env: &mut Environment) -> Result<Value, EvalError> {
+fn eval_function_call(call: &Expr, elements: &[Expr], env: &Environment) -> Result<Tail, EvalError> {
     if elements.is_empty() {
-        return Ok(Value::List(vec![]));
+        return Ok(Tail::Value(Value::List(vec![])));
     }
     
     let func_expr = &elements[0];
@@ -680,45 +2851,54 @@ fn eval_function_call(elements: &[Expr], env: &mut Environment) -> Result<Value,
     // Evaluate function
     let func = eval_expr(func_expr, env)?;
     
+    // Macros receive their arguments unevaluated, and the code they expand
+    // into is evaluated in place of the call
+    if let Value::Macro { params, body, closure } = &func {
+        return expand_macro_call(params, body, closure, call, args_exprs, env);
+    }
+    
     // Evaluate arguments
     let mut args = Vec::new();
     for arg_expr in args_exprs {
         args.push(eval_expr(arg_expr, env)?);
     }
     
-    // Call function
+    call_function(func, args, call, env)
+}
+
+/// Expands a macro call and evaluates the expansion in place of the call.
+fn expand_macro_call(
+    params: &Params,
+    body: &[Expr],
+    closure: &Environment,
+    call: &Expr,
+    args_exprs: &[Expr],
+    env: &Environment,
+) -> Result<Tail, EvalError> {
+    let forms = args_exprs.iter().map(Value::from_expr).collect();
+    let expansion = expand_macro(params, body, closure, forms)?;
+    Ok(Tail::Eval(expansion.to_expr_at(call.span)?, env.clone()))
+}
+
+/// Calls an evaluated function with evaluated arguments. Kept apart from
+/// `eval_function_call`, whose frame stays on the Rust stack while the
+/// arguments are evaluated.
+fn call_function(func: Value, args: Vec<Value>, call: &Expr, env: &Environment) -> Result<Tail, EvalError> {
     match func {
-        Value::Function(f) => f(&args),
-        Value::Lambda { params, body, mut closure } => {
-            // Check arity
-            if args.len() != params.len() {
-                return Err(EvalError::ArityError(format!(
-                    "Function expects {} arguments, got {}",
-                    params.len(),
-                    args.len()
-                )));
-            }
-            
-            // Merge current environment into closure for recursive calls
-            for (name, value) in &env.bindings {
-                if !closure.bindings.contains_key(name) {
-                    closure.define(name, value.clone());
-                }
-            }
-            
-            // Bind arguments to parameters in closure environment
-            for (param, arg) in params.iter().zip(args.iter()) {
-                closure.define(param, arg.clone());
-            }
+        Value::Function(f) => Ok(Tail::Value(f(&args)?)),
+        Value::EnvFunction(f) => Ok(Tail::Value(f(&args, env).map_err(|err| err.called_from(call.span))?)),
+        Value::Lambda { name, params, body, closure } => {
+            let frame = bind_arguments(&params, args, &closure)?;
+            let call = CallFrame {
+                function: name.unwrap_or_else(|| "<lambda>".into()),
+                call_site: call.span,
+            };
             
-            // Evaluate body expressions in sequence, return last result
-            let mut result = Value::Nil;
-            for expr in &body {
-                result = eval_expr(expr, &mut closure)?;
-            }
-            Ok(result)
+            // Evaluate body expressions in sequence, the last one in tail position
+            eval_call_body(&body, &frame, call)
         }
-        _ => Err(EvalError::InvalidFunction(format!("Not a function: {:?}", func))),
+        Value::Keyword(keyword) => Ok(Tail::Value(keyword_lookup(&keyword, &args)?)),
+        _ => Err(ErrorKind::InvalidFunction(format!("Not a function: {:?}", func)).into()),
     }
 }
 
@@ -726,45 +2906,366 @@ impl std::fmt::Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Value::Number(n) => write!(f, "{}", n),
-            Value::String(s) => write!(f, "\"{}\"", s),
+            Value::String(s) => write!(f, "\"{}\"", escape_string(s)),
             Value::Symbol(s) => write!(f, "{}", s),
+            Value::Keyword(k) => write!(f, "{}", k),
+            Value::Char(ch) => write!(f, "{}", char_literal(*ch)),
             Value::Boolean(b) => write!(f, "{}", if *b { "true" } else { "false" }),
-            Value::List(elements) => {
-                write!(f, "(")?;
-                for (i, elem) in elements.iter().enumerate() {
-                    if i > 0 {
-                        write!(f, " ")?;
-                    }
-                    write!(f, "{}", elem)?;
-                }
-                write!(f, ")")
-            }
-            Value::Function(_) => write!(f, "<function>"),
-            Value::Lambda { params, .. } => {
-                write!(f, "<lambda (")?;
-                for (i, param) in params.iter().enumerate() {
-                    if i > 0 {
-                        write!(f, " ")?;
-                    }
-                    write!(f, "{}", param)?;
+            Value::List(elements) => write_values(f, "(", elements.iter(), ")"),
+            Value::Vector(elements) => write_values(f, "[", elements.iter(), "]"),
+            Value::Map(map) => write_values(f, "{", map.iter().flat_map(|(key, value)| [key, value]), "}"),
+            Value::Set(set) => write_values(f, "#{", set.iter(), "}"),
+            Value::Function(_) | Value::EnvFunction(_) => write!(f, "<function>"),
+            Value::Lambda { params, .. } => write!(f, "<lambda {}>", params),
+            Value::Macro { params, .. } => write!(f, "<macro {}>", params),
+            Value::Box(cell) => write!(f, "<box {}>", cell.borrow()),
+            Value::Error(kind) => write!(f, "<error {}: {}>", kind.name(), kind),
+            Value::Environment(_) => write!(f, "<environment>"),
+            Value::Nil => write!(f, "nil"),
+        }
+    }
+}
+
+fn write_values<'a>(
+    f: &mut std::fmt::Formatter<'_>,
+    open: &str,
+    values: impl Iterator<Item = &'a Value>,
+    close: &str,
+) -> std::fmt::Result {
+    write!(f, "{}", open)?;
+    for (i, value) in values.enumerate() {
+        if i > 0 {
+            write!(f, " ")?;
+        }
+        write!(f, "{}", value)?;
+    }
+    write!(f, "{}", close)
+}
+
+impl std::fmt::Display for Params {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts: Vec<String> = self.required.clone();
+        if !self.optional.is_empty() {
+            parts.push("&optional".to_string());
+            for (name, default) in &self.optional {
+                match default {
+                    Some(default) => parts.push(format!("({} {})", name, default)),
+                    None => parts.push(name.clone()),
                 }
-                write!(f, ")>")
             }
-            Value::Nil => write!(f, "nil"),
         }
+        if let Some(rest) = &self.rest {
+            parts.push("&rest".to_string());
+            parts.push(rest.clone());
+        }
+        write!(f, "({})", parts.join(" "))
     }
 }
 
-impl std::fmt::Display for EvalError {
+impl std::fmt::Display for ErrorKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            EvalError::UndefinedSymbol(s) => write!(f, "Undefined symbol: {}", s),
-            EvalError::TypeError(msg) => write!(f, "Type error: {}", msg),
-            EvalError::ArityError(msg) => write!(f, "Arity error: {}", msg),
-            EvalError::DivisionByZero => write!(f, "Division by zero"),
-            EvalError::InvalidFunction(msg) => write!(f, "Invalid function: {}", msg),
+            ErrorKind::UndefinedSymbol(s) => write!(f, "Undefined symbol: {}", s),
+            ErrorKind::TypeError(msg) => write!(f, "Type error: {}", msg),
+            ErrorKind::ArityError(msg) => write!(f, "Arity error: {}", msg),
+            ErrorKind::DivisionByZero => write!(f, "Division by zero"),
+            ErrorKind::InvalidFunction(msg) => write!(f, "Invalid function: {}", msg),
+            ErrorKind::IndexError(msg) => write!(f, "Index error: {}", msg),
+            ErrorKind::ModuleError(msg) => write!(f, "Module error: {}", msg),
+            ErrorKind::ReadError(msg) => write!(f, "Read error: {}", msg),
+            ErrorKind::RecursionError => write!(f, "Recursion error: maximum recursion depth exceeded"),
+            ErrorKind::Thrown(value) => write!(f, "Uncaught exception: {}", value),
         }
     }
 }
 
-impl std::error::Error for EvalError {}
\ No newline at end of file
+impl std::fmt::Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
+impl std::error::Error for EvalError {}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn error_kind_names_are_listed() {
+        let kinds = [
+            ErrorKind::UndefinedSymbol(String::new()),
+            ErrorKind::TypeError(String::new()),
+            ErrorKind::ArityError(String::new()),
+            ErrorKind::DivisionByZero,
+            ErrorKind::InvalidFunction(String::new()),
+            ErrorKind::IndexError(String::new()),
+            ErrorKind::ModuleError(String::new()),
+            ErrorKind::ReadError(String::new()),
+            ErrorKind::RecursionError,
+            ErrorKind::Thrown(Box::new(Value::Nil)),
+        ];
+        let names: Vec<&str> = kinds.iter().map(ErrorKind::name).collect();
+        assert_eq!(names, ErrorKind::NAMES);
+    }
+    
+    fn read(source: &str) -> Expr {
+        parser::parse(tokenizer::tokenize(source).unwrap()).unwrap().remove(0)
+    }
+    
+    /// Checks that converting `value` to code and back gives `value` again.
+    fn assert_round_trip(value: Value) {
+        let expr = Expr::from_value(&value).unwrap();
+        assert_eq!(expr.to_value(), value, "via {}", expr);
+    }
+    
+    #[test]
+    fn data_round_trips_through_code() {
+        let keyword = Value::Keyword(Keyword::new("k"));
+        let list = Value::List(vec![
+            Value::Symbol("f".to_string()),
+            Value::Number(Number::Int(1)),
+            Value::String("s\n".to_string()),
+            Value::Char('λ'),
+            Value::Boolean(false),
+            Value::Nil,
+            Value::List(vec![]),
+        ]);
+        
+        assert_round_trip(keyword.clone());
+        assert_round_trip(Value::Char(' '));
+        assert_round_trip(Value::Nil);
+        assert_round_trip(list.clone());
+        assert_round_trip(Value::Vector(vec![list.clone(), keyword.clone()]));
+        let entries = [(keyword.clone(), list.clone()), (Value::Number(Number::Int(2)), Value::Nil)];
+        assert_round_trip(Value::Map(Box::new(entries.into_iter().collect())));
+        assert_round_trip(Value::Set(Box::new([keyword, Value::Char('a'), Value::Vector(vec![])].into_iter().collect())));
+    }
+    
+    #[test]
+    fn quote_forms_round_trip() {
+        for name in ["quote", "quasiquote", "unquote", "unquote-splicing"] {
+            assert_round_trip(quoted_form(name, Value::Symbol("x".to_string())));
+        }
+        // Only two-element lists are quote forms
+        assert_round_trip(Value::List(vec![
+            Value::Symbol("quote".to_string()),
+            Value::Symbol("a".to_string()),
+            Value::Symbol("b".to_string()),
+        ]));
+    }
+    
+    #[test]
+    fn code_round_trips_through_data() {
+        for source in ["(f 1 \"s\" #\\a :k [x nil] {:a (g 'b)} #{true})", "`(a ,b ,@c)", "''x", "()"] {
+            let expr = read(source);
+            assert_eq!(Value::from_expr(&expr).to_expr().unwrap(), expr, "{}", source);
+        }
+    }
+    
+    #[test]
+    fn runtime_values_have_no_code() {
+        let env = Environment::new();
+        let closure = eval_expr(&read("(lambda (x) x)"), &env).unwrap();
+        for value in [
+            closure,
+            Value::Function(builtin_add),
+            Value::Box(Rc::new(RefCell::new(Value::Nil))),
+            Value::Environment(env.clone()),
+            Value::List(vec![Value::Function(builtin_add)]),
+        ] {
+            let err = value.to_expr().unwrap_err();
+            assert!(matches!(err.kind, ErrorKind::TypeError(_)), "{}", value);
+        }
+    }
+    
+    /// Evaluates every form in `source`, giving the value of the last.
+    fn run(source: &str) -> Result<Value, EvalError> {
+        let env = Environment::new();
+        let exprs = parser::parse(tokenizer::tokenize(source).unwrap()).unwrap();
+        eval_sequence(&exprs, &env)
+    }
+    
+    /// Like `run`, on a thread with the stack the interpreter is given,
+    /// printing the value or error.
+    fn run_deep(source: &'static str) -> String {
+        let thread = std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(move || match run(source) {
+                Ok(value) => value.to_string(),
+                Err(err) => err.to_string(),
+            });
+        thread.unwrap().join().unwrap()
+    }
+    
+    #[test]
+    fn deep_recursion_is_a_catchable_error() {
+        let source = "
+            (defn f (n) (if (= n 0) 0 (+ 1 (f (- n 1)))))
+            (list (f 5000) (try (f 100000) (catch recursion-error e (error-kind e))))";
+        assert_eq!(run_deep(source), "(5000 recursion-error)");
+        
+        // The depth is given back once the error is caught
+        let source = "
+            (defn f (n) (if (= n 0) 0 (+ 1 (f (- n 1)))))
+            (try (f 100000) (catch any e nil))
+            (f 5000)";
+        assert_eq!(run_deep(source), "5000");
+    }
+    
+    /// Evaluates `source` after defining `x` as 1 and `xs` as (2 3), and
+    /// prints the result.
+    fn quasiquote(source: &str) -> String {
+        match run(&format!("(def x 1) (def xs '(2 3)) {}", source)) {
+            Ok(value) => value.to_string(),
+            Err(err) => err.to_string(),
+        }
+    }
+    
+    #[test]
+    fn quasiquote_evaluates_unquotes_at_depth_one() {
+        assert_eq!(quasiquote("`(a ,x ,@xs b)"), "(a 1 2 3 b)");
+        assert_eq!(quasiquote("`[,@xs ,x]"), "[2 3 1]");
+        assert_eq!(quasiquote("`{:k ,x}"), "{:k 1}");
+        assert_eq!(quasiquote("`(a ,@nil b)"), "(a b)");
+        assert_eq!(quasiquote("`(a 'x ,'x)"), "(a (quote x) x)");
+    }
+    
+    #[test]
+    fn nested_quasiquotes_keep_inner_unquotes() {
+        // Each quasiquote adds a level and each unquote removes one; only
+        // unquotes that bring the level to zero are evaluated
+        assert_eq!(quasiquote("`(a `(b ,(c ,x)))"), "(a (quasiquote (b (unquote (c 1)))))");
+        assert_eq!(quasiquote("`(a `(b ,,x))"), "(a (quasiquote (b (unquote 1))))");
+        assert_eq!(quasiquote("`(a `(b ,@xs ,@,xs))"), "(a (quasiquote (b (unquote-splicing xs) (unquote-splicing (2 3)))))");
+        assert_eq!(quasiquote("``,x"), "(quasiquote (unquote x))");
+    }
+    
+    #[test]
+    fn misplaced_unquotes_are_errors() {
+        assert_eq!(quasiquote("`(a ,@x)"), "Type error: unquote-splicing requires a list, got 1");
+        assert_eq!(quasiquote("`,@xs"), "Type error: unquote-splicing must appear inside a list");
+        assert_eq!(quasiquote(",x"), "Type error: unquote used outside of quasiquote");
+    }
+    
+    #[test]
+    fn macros_expand_through_quasiquote() {
+        let source = "
+            (defmacro unless (c . body) `(if ,c nil (do ,@body)))
+            (list (macroexpand-1 '(unless false 1 2)) (unless false 1 2) (unless true (car 1)))";
+        assert_eq!(run(source).unwrap().to_string(), "((if false nil (do 1 2)) 2 nil)");
+    }
+    
+    /// Runs `source` with a `note` function that logs its argument, and
+    /// prints the result followed by everything noted, in order.
+    fn with_log(source: &str) -> String {
+        let source = format!("
+            (def log (box '()))
+            (defn note (x) (set-box! log (cons x (unbox log))) x)
+            (list {} (reverse (unbox log)))", source);
+        run(&source).unwrap().to_string()
+    }
+    
+    #[test]
+    fn finally_runs_after_the_body_or_handler() {
+        assert_eq!(
+            with_log("(try (note 'body) (catch any e (note 'catch)) (finally (note 'finally)))"),
+            "(body (body finally))"
+        );
+        assert_eq!(
+            with_log("(try (do (note 'body) (car 1) (note 'after)) (catch type-error e (note 'catch)) (finally (note 'finally)))"),
+            "(catch (body catch finally))"
+        );
+    }
+    
+    #[test]
+    fn the_first_matching_catch_handles_an_error() {
+        let source = "
+            (try (car 1)
+              (catch arity-error e (note 'arity))
+              (catch type-error e (note 'type))
+              (catch any e (note 'any)))";
+        assert_eq!(with_log(source), "(type (type))");
+        assert_eq!(with_log("(try (throw {:code 42}) (catch user e (:code e)))"), "(42 ())");
+    }
+    
+    #[test]
+    fn uncaught_errors_run_finally_on_the_way_out() {
+        let source = "
+            (try
+              (try (car 1) (catch arity-error e (note 'inner)) (finally (note 'inner-finally)))
+              (catch type-error e (note 'outer)))";
+        assert_eq!(with_log(source), "(outer (inner-finally outer))");
+        
+        // Errors raised by a handler leave through finally too
+        let source = "
+            (try
+              (try (car 1) (catch type-error e (throw 'again)) (finally (note 'finally)))
+              (catch user e (list 'rethrown e)))";
+        assert_eq!(with_log(source), "((rethrown again) (finally))");
+    }
+    
+    #[test]
+    fn unknown_catch_kinds_are_errors() {
+        // The clause is checked before the body runs
+        let source = "(try (try (note 'body) (catch typo-error e nil)) (catch type-error e (error-message e)))";
+        assert_eq!(with_log(source), "(\"Type error: unknown error kind in catch: typo-error\" ())");
+        
+        for name in ErrorKind::NAMES.iter().chain(&["any"]) {
+            assert!(run(&format!("(try 1 (catch {} e nil))", name)).is_ok(), "{}", name);
+        }
+    }
+    
+    #[test]
+    fn keyword_rejects_unreadable_names() {
+        assert_eq!(run("(list (keyword \"born\") (keyword 'x) (keyword :k))").unwrap().to_string(), "(:born :x :k)");
+        for name in ["\"\"", "\"a b\"", "\"a:b\"", "\"(\""] {
+            let kind = run(&format!("(keyword {})", name)).unwrap_err().kind;
+            assert!(matches!(kind, ErrorKind::TypeError(_)), "{}", name);
+        }
+    }
+    
+    #[test]
+    fn tail_calls_run_in_constant_stack() {
+        // A million nested calls would overflow the test thread's stack
+        let source = "
+            (defn count-up (n acc) (if (= n 0) acc (count-up (- n 1) (+ acc 1))))
+            (list (count-up 1000000 0) (let loop ((i 0)) (if (< i 100000) (loop (+ i 1)) i)))";
+        assert_eq!(run(source).unwrap().to_string(), "(1000000 100000)");
+        
+        let source = "
+            (defn my-even? (n) (cond ((= n 0) true) (else (my-odd? (- n 1)))))
+            (defn my-odd? (n) (and (not (= n 0)) (my-even? (- n 1))))
+            (list (my-even? 10001) (my-odd? 10001))";
+        assert_eq!(run(source).unwrap().to_string(), "(false true)");
+        
+        // Each tail call replaces its caller's frame in the backtrace too
+        let source = "(defn down (n) (if (= n 0) (car 1) (down (- n 1)))) (down 10000)";
+        let err = run(source).unwrap_err();
+        let functions: Vec<&str> = err.backtrace.iter().map(|frame| &*frame.function).collect();
+        assert_eq!(functions, ["down"]);
+    }
+    
+    #[test]
+    fn def_binds_at_top_level() {
+        let source = "
+            (def counter 0)
+            (defn bump () (def counter (+ counter 1)))
+            (bump)
+            (let ((x 1)) (defn get-x () x))
+            (list counter (get-x))";
+        assert_eq!(run(source).unwrap().to_string(), "(1 1)");
+        
+        // Parameters and let bindings stay local, shadowing the definition
+        let source = "
+            (defn shadow (x) (def x 5) x)
+            (list (shadow 1) x (let ((y 2)) (def y 3) y) y)";
+        assert_eq!(run(source).unwrap().to_string(), "(1 5 2 3)");
+        
+        // A separate environment has a top level of its own
+        let source = "
+            (def sandbox (make-environment))
+            (eval '((lambda () (def z 1))) sandbox)
+            (list (eval 'z sandbox) (try z (catch undefined-symbol e nil)))";
+        assert_eq!(run(source).unwrap().to_string(), "(1 nil)");
+    }
+}
@@ -1,8 +1,11 @@
 use std::fs;
+use std::path::{Path, PathBuf};
 use std::process;
-use crate::{tokenizer, parser, evaluator};
+use crate::{tokenizer, parser, evaluator, span};
 
-pub fn execute_file(filename: &str, debug: bool) {
+/// Runs a source file. Modules it requires are looked for beside it first,
+/// then in the directories of `module_path`.
+pub fn execute_file(filename: &str, debug: bool, backtrace_depth: usize, module_path: Vec<PathBuf>) {
     let content = match fs::read_to_string(filename) {
         Ok(content) => content,
         Err(err) => {
@@ -17,13 +20,15 @@ pub fn execute_file(filename: &str, debug: bool) {
         println!("---");
     }
     
-    let tokens = tokenizer::tokenize(&content);
-    
-    if tokens.is_none() {
-        eprintln!("Error tokenizing file '{}'", filename);
-        process::exit(1);
-    }
-    let tokens = tokens.unwrap();
+    let source = span::SourceId::register(filename, &content);
+    let tokens = match tokenizer::tokenize_source(&content, source) {
+        Ok(tokens) => tokens,
+        Err(err) => {
+            let message = format!("Tokenize error: {}", err);
+            eprintln!("{}", span::format_error(filename, &content, &message, Some(err.span())));
+            process::exit(1);
+        }
+    };
 
     if debug {
         println!("Tokens ({} total):", tokens.len());
@@ -45,15 +50,22 @@ pub fn execute_file(filename: &str, debug: bool) {
             expressions
         }
         Err(err) => {
-            eprintln!("Parse error: {}", err);
+            let message = format!("Parse error: {}", err);
+            eprintln!("{}", span::format_error(filename, &content, &message, Some(err.span())));
             process::exit(1);
         }
     };
     
-    let mut env = evaluator::Environment::new();
+    let env = evaluator::Environment::new();
+    let file_dir = match Path::new(filename).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let search_path = std::iter::once(file_dir).chain(module_path).collect();
+    env.modules().borrow_mut().set_search_path(search_path);
     
     for (i, expr) in expressions.iter().enumerate() {
-        match evaluator::eval_expr(expr, &mut env) {
+        match evaluator::eval_expr(expr, &env) {
             Ok(value) => {
                 if debug {
                     println!("Expression {}: {} => {}", i, expr, value);
@@ -61,7 +73,11 @@ pub fn execute_file(filename: &str, debug: bool) {
                 // Don't print results implicitly - only explicit print calls show output
             }
             Err(err) => {
-                eprintln!("Evaluation error in expression {}: {}", i, err);
+                let message = format!("Evaluation error: {}", err);
+                eprintln!("{}", span::format_error(filename, &content, &message, err.span));
+                if let Some(backtrace) = err.backtrace_report(filename, backtrace_depth) {
+                    eprintln!("{}", backtrace);
+                }
                 process::exit(1);
             }
         }
@@ -0,0 +1,61 @@
+//! Keywords: names like `:name` that evaluate to themselves. Each distinct
+//! name is stored once, so keywords compare and hash by pointer instead of
+//! by their text.
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+thread_local! {
+    static INTERNED: RefCell<HashSet<Rc<str>>> = RefCell::new(HashSet::new());
+}
+
+#[derive(Clone)]
+pub struct Keyword(Rc<str>);
+
+impl Keyword {
+    /// Returns the keyword called `name`, which is given without its colon.
+    pub fn new(name: &str) -> Self {
+        INTERNED.with(|interned| {
+            let mut interned = interned.borrow_mut();
+            if let Some(existing) = interned.get(name) {
+                return Keyword(existing.clone());
+            }
+            
+            let name: Rc<str> = Rc::from(name);
+            interned.insert(name.clone());
+            Keyword(name)
+        })
+    }
+    
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq for Keyword {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Keyword {}
+
+impl Hash for Keyword {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::ptr::hash(Rc::as_ptr(&self.0), state);
+    }
+}
+
+impl std::fmt::Debug for Keyword {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, ":{}", self.0)
+    }
+}
+
+impl std::fmt::Display for Keyword {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, ":{}", self.0)
+    }
+}
@@ -1,5 +1,12 @@
 pub mod repl;
+pub mod line_editor;
 pub mod file_exec;
 pub mod tokenizer;
 pub mod parser;
-pub mod evaluator;
\ No newline at end of file
+pub mod evaluator;
+pub mod span;
+pub mod number;
+pub mod bigint;
+pub mod collections;
+pub mod keyword;
+pub mod module;
\ No newline at end of file
@@ -0,0 +1,482 @@
+//! A small line editor for ANSI terminals. The terminal is put into raw mode
+//! through `stty` only while a line is being read, so output from evaluation
+//! and Ctrl+C during a long computation behave as usual. When stdin isn't a
+//! terminal, lines are read plainly so input can still be piped in.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// The most history entries kept in memory and in the history file.
+const MAX_HISTORY: usize = 1000;
+
+/// Characters that end the word being completed.
+const WORD_BREAKS: &str = "()[]{}'`,@\"";
+
+pub enum ReadLine {
+    Line(String),
+    /// Ctrl+C was pressed; the line was abandoned.
+    Interrupted,
+    Eof,
+}
+
+enum Key {
+    Char(char),
+    Ctrl(char),
+    Enter,
+    Tab,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Unknown,
+}
+
+/// The line being edited and the position of the cursor within it.
+#[derive(Clone, Default)]
+struct LineState {
+    buffer: Vec<char>,
+    cursor: usize,
+}
+
+impl LineState {
+    fn set(&mut self, line: &str) {
+        self.buffer = line.chars().collect();
+        self.cursor = self.buffer.len();
+    }
+    
+    fn insert(&mut self, text: &str) {
+        for ch in text.chars() {
+            self.buffer.insert(self.cursor, ch);
+            self.cursor += 1;
+        }
+    }
+    
+    fn text(&self) -> String {
+        self.buffer.iter().collect()
+    }
+    
+    /// Where the word ending at the cursor starts.
+    fn word_start(&self) -> usize {
+        self.buffer[..self.cursor]
+            .iter()
+            .rposition(|&ch| ch.is_whitespace() || WORD_BREAKS.contains(ch))
+            .map_or(0, |i| i + 1)
+    }
+    
+    fn refresh(&self, out: &mut impl Write, prompt: &str) -> io::Result<()> {
+        write!(out, "\r{}{}\x1b[K", prompt, self.text())?;
+        let back = self.buffer.len() - self.cursor;
+        if back > 0 {
+            write!(out, "\x1b[{}D", back)?;
+        }
+        out.flush()
+    }
+}
+
+pub struct LineEditor {
+    history: Vec<String>,
+    history_path: Option<PathBuf>,
+    interactive: bool,
+}
+
+impl LineEditor {
+    /// Creates an editor, loading any history previously saved to
+    /// `history_path`.
+    pub fn new(history_path: Option<PathBuf>) -> Self {
+        let mut history: Vec<String> = history_path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|contents| contents.lines().filter(|line| !line.is_empty()).map(String::from).collect())
+            .unwrap_or_default();
+        
+        // Keep the history file from growing without bound
+        if history.len() > MAX_HISTORY {
+            history.drain(..history.len() - MAX_HISTORY);
+            if let Some(path) = &history_path {
+                let _ = fs::write(path, history.join("\n") + "\n");
+            }
+        }
+        
+        let interactive = io::stdin().is_terminal()
+            && io::stdout().is_terminal()
+            && std::env::var("TERM").map_or(true, |term| term != "dumb");
+        
+        Self {
+            history,
+            history_path,
+            interactive,
+        }
+    }
+    
+    /// Records `line` in the history and appends it to the history file.
+    /// Blank lines and repeats of the previous entry are skipped.
+    pub fn add_history(&mut self, line: &str) {
+        if line.trim().is_empty() || self.history.last().is_some_and(|last| last == line) {
+            return;
+        }
+        
+        self.history.push(line.to_string());
+        if self.history.len() > MAX_HISTORY {
+            self.history.remove(0);
+        }
+        
+        // History is a convenience, so failing to save it isn't an error
+        if let Some(path) = &self.history_path
+            && let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path)
+        {
+            let _ = writeln!(file, "{}", line);
+        }
+    }
+    
+    /// Reads a line after showing `prompt`. On Tab, `complete` is called with
+    /// the word before the cursor and returns the candidates it could become.
+    pub fn read_line(&mut self, prompt: &str, complete: &dyn Fn(&str) -> Vec<String>) -> io::Result<ReadLine> {
+        if self.interactive {
+            match RawMode::enable() {
+                Ok(_raw) => return self.edit(prompt, complete),
+                // Without stty there's no raw mode; fall back to plain input
+                Err(_) => self.interactive = false,
+            }
+        }
+        
+        print!("{}", prompt);
+        io::stdout().flush()?;
+        
+        let mut line = String::new();
+        if io::stdin().read_line(&mut line)? == 0 {
+            return Ok(ReadLine::Eof);
+        }
+        
+        let len = line.trim_end_matches(['\n', '\r']).len();
+        line.truncate(len);
+        Ok(ReadLine::Line(line))
+    }
+    
+    fn edit(&mut self, prompt: &str, complete: &dyn Fn(&str) -> Vec<String>) -> io::Result<ReadLine> {
+        let mut input = io::stdin().lock();
+        let mut out = io::stdout().lock();
+        
+        let mut line = LineState::default();
+        // Position in the history while browsing it with Up and Down, and the
+        // line that was being typed before browsing started
+        let mut history_index = self.history.len();
+        let mut unsaved = String::new();
+        let mut last_was_tab = false;
+        
+        line.refresh(&mut out, prompt)?;
+        
+        loop {
+            let mut key = match read_key(&mut input)? {
+                Some(key) => key,
+                None => return Ok(ReadLine::Eof),
+            };
+            
+            if let Key::Ctrl('r') = key {
+                match self.reverse_search(&mut input, &mut out, &mut line)? {
+                    Some(next) => key = next,
+                    None => {
+                        line.refresh(&mut out, prompt)?;
+                        continue;
+                    }
+                }
+                line.refresh(&mut out, prompt)?;
+            }
+            
+            let is_tab = matches!(key, Key::Tab);
+            
+            match key {
+                Key::Enter => {
+                    writeln!(out)?;
+                    return Ok(ReadLine::Line(line.text()));
+                }
+                
+                Key::Ctrl('c') => {
+                    writeln!(out, "^C")?;
+                    return Ok(ReadLine::Interrupted);
+                }
+                
+                Key::Ctrl('d') if line.buffer.is_empty() => {
+                    writeln!(out)?;
+                    return Ok(ReadLine::Eof);
+                }
+                
+                Key::Char(ch) => line.insert(&ch.to_string()),
+                
+                Key::Backspace | Key::Ctrl('h') if line.cursor > 0 => {
+                    line.cursor -= 1;
+                    line.buffer.remove(line.cursor);
+                }
+                
+                Key::Delete | Key::Ctrl('d') if line.cursor < line.buffer.len() => {
+                    line.buffer.remove(line.cursor);
+                }
+                
+                Key::Left | Key::Ctrl('b') => line.cursor = line.cursor.saturating_sub(1),
+                Key::Right | Key::Ctrl('f') => line.cursor = (line.cursor + 1).min(line.buffer.len()),
+                Key::Home | Key::Ctrl('a') => line.cursor = 0,
+                Key::End | Key::Ctrl('e') => line.cursor = line.buffer.len(),
+                
+                Key::Ctrl('k') => line.buffer.truncate(line.cursor),
+                
+                Key::Ctrl('u') => {
+                    line.buffer.drain(..line.cursor);
+                    line.cursor = 0;
+                }
+                
+                Key::Ctrl('w') => {
+                    let mut start = line.cursor;
+                    while start > 0 && line.buffer[start - 1].is_whitespace() {
+                        start -= 1;
+                    }
+                    while start > 0 && !line.buffer[start - 1].is_whitespace() {
+                        start -= 1;
+                    }
+                    line.buffer.drain(start..line.cursor);
+                    line.cursor = start;
+                }
+                
+                Key::Ctrl('l') => write!(out, "\x1b[H\x1b[2J")?,
+                
+                Key::Up | Key::Ctrl('p') if history_index > 0 => {
+                    if history_index == self.history.len() {
+                        unsaved = line.text();
+                    }
+                    history_index -= 1;
+                    line.set(&self.history[history_index]);
+                }
+                
+                Key::Down | Key::Ctrl('n') if history_index < self.history.len() => {
+                    history_index += 1;
+                    match self.history.get(history_index) {
+                        Some(entry) => line.set(entry),
+                        None => line.set(&unsaved),
+                    }
+                }
+                
+                Key::Tab => {
+                    let start = line.word_start();
+                    let word: String = line.buffer[start..line.cursor].iter().collect();
+                    let mut candidates = complete(&word);
+                    candidates.sort();
+                    candidates.dedup();
+                    
+                    let common = common_prefix(&candidates);
+                    if common.len() > word.len() {
+                        line.insert(&common[word.len()..]);
+                    } else if candidates.len() > 1 && last_was_tab {
+                        // A second Tab with nothing more to fill in lists the options
+                        writeln!(out)?;
+                        writeln!(out, "{}", candidates.join("  "))?;
+                    } else {
+                        write!(out, "\x07")?;
+                    }
+                }
+                
+                _ => {}
+            }
+            
+            last_was_tab = is_tab;
+            line.refresh(&mut out, prompt)?;
+        }
+    }
+    
+    /// Searches the history backwards for lines containing what is typed,
+    /// showing the latest match in `line`. Ctrl+R moves to the next older
+    /// match and Ctrl+G gives up, restoring the original line. Any other key
+    /// keeps the match and is returned to be handled as usual.
+    fn reverse_search(
+        &self,
+        input: &mut impl Read,
+        out: &mut impl Write,
+        line: &mut LineState,
+    ) -> io::Result<Option<Key>> {
+        let original = line.clone();
+        let mut query = String::new();
+        let mut found: Option<usize> = None;
+        let mut failed = false;
+        
+        loop {
+            write!(
+                out,
+                "\r({}reverse-i-search)`{}': {}\x1b[K",
+                if failed { "failed " } else { "" },
+                query,
+                line.text()
+            )?;
+            out.flush()?;
+            
+            // Where to search back from: the current match for a longer query,
+            // just before it for Ctrl+R, or the newest entry after a Backspace
+            let before = match read_key(input)? {
+                Some(Key::Char(ch)) => {
+                    query.push(ch);
+                    found.map_or(self.history.len(), |i| i + 1)
+                }
+                Some(Key::Ctrl('r')) => found.unwrap_or(self.history.len()),
+                Some(Key::Backspace) => {
+                    query.pop();
+                    self.history.len()
+                }
+                Some(Key::Ctrl('g')) | Some(Key::Ctrl('c')) => {
+                    *line = original;
+                    return Ok(None);
+                }
+                Some(key) => return Ok(Some(key)),
+                None => return Ok(Some(Key::Ctrl('d'))),
+            };
+            
+            let result = (0..before).rev().find_map(|i| {
+                self.history[i].find(&query).map(|offset| (i, offset))
+            });
+            
+            failed = result.is_none();
+            if let Some((index, offset)) = result {
+                let entry = &self.history[index];
+                found = Some(index);
+                line.set(entry);
+                line.cursor = entry[..offset].chars().count();
+            }
+        }
+    }
+}
+
+/// The longest prefix shared by all of `candidates`.
+fn common_prefix(candidates: &[String]) -> &str {
+    let Some(first) = candidates.first() else {
+        return "";
+    };
+    
+    let mut prefix = first.as_str();
+    for candidate in &candidates[1..] {
+        let len = prefix
+            .char_indices()
+            .zip(candidate.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(prefix.len().min(candidate.len()), |((i, _), _)| i);
+        prefix = &prefix[..len];
+    }
+    prefix
+}
+
+fn read_byte(input: &mut impl Read) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    match input.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+/// Reads one keypress, decoding UTF-8 characters and the escape sequences
+/// sent by arrow and editing keys. Returns `None` at the end of input.
+fn read_key(input: &mut impl Read) -> io::Result<Option<Key>> {
+    let Some(byte) = read_byte(input)? else {
+        return Ok(None);
+    };
+    
+    let key = match byte {
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        0x7f => Key::Backspace,
+        0x1b => read_escape_sequence(input)?,
+        1..=26 => Key::Ctrl((b'a' + byte - 1) as char),
+        0..=0x7f => Key::Char(byte as char),
+        _ => {
+            // The leading byte says how many continuation bytes follow
+            let len = match byte {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                _ => 4,
+            };
+            let mut bytes = vec![byte];
+            for _ in 1..len {
+                match read_byte(input)? {
+                    Some(next) => bytes.push(next),
+                    None => break,
+                }
+            }
+            match std::str::from_utf8(&bytes).ok().and_then(|s| s.chars().next()) {
+                Some(ch) => Key::Char(ch),
+                None => Key::Unknown,
+            }
+        }
+    };
+    
+    Ok(Some(key))
+}
+
+fn read_escape_sequence(input: &mut impl Read) -> io::Result<Key> {
+    match read_byte(input)? {
+        Some(b'[') => {
+            // Parameters, then a final byte such as `A` or `~`
+            let mut params = String::new();
+            loop {
+                match read_byte(input)? {
+                    Some(byte @ 0x40..=0x7e) => {
+                        return Ok(match (byte, params.as_str()) {
+                            (b'A', _) => Key::Up,
+                            (b'B', _) => Key::Down,
+                            (b'C', _) => Key::Right,
+                            (b'D', _) => Key::Left,
+                            (b'H', _) | (b'~', "1") | (b'~', "7") => Key::Home,
+                            (b'F', _) | (b'~', "4") | (b'~', "8") => Key::End,
+                            (b'~', "3") => Key::Delete,
+                            _ => Key::Unknown,
+                        });
+                    }
+                    Some(byte) => params.push(byte as char),
+                    None => return Ok(Key::Unknown),
+                }
+            }
+        }
+        Some(b'O') => Ok(match read_byte(input)? {
+            Some(b'A') => Key::Up,
+            Some(b'B') => Key::Down,
+            Some(b'C') => Key::Right,
+            Some(b'D') => Key::Left,
+            Some(b'H') => Key::Home,
+            Some(b'F') => Key::End,
+            _ => Key::Unknown,
+        }),
+        _ => Ok(Key::Unknown),
+    }
+}
+
+/// Keeps the terminal in raw mode until dropped: input is delivered a byte
+/// at a time, unechoed, with Ctrl+C and friends arriving as ordinary bytes.
+struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        let saved = stty(&["-g"])?.trim().to_string();
+        stty(&["-icanon", "-echo", "-isig", "-iexten", "-ixon", "min", "1", "time", "0"])?;
+        Ok(Self { saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .output()?;
+    
+    if !output.status.success() {
+        return Err(io::Error::other("stty failed"));
+    }
+    
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -1,5 +1,8 @@
 use clap::Parser;
-use replisp::{repl, file_exec};
+use replisp::{repl, file_exec, evaluator};
+use std::path::PathBuf;
+use std::process;
+use std::thread;
 
 #[derive(Parser)]
 #[command(name = "replisp")]
@@ -12,25 +15,56 @@ struct Args {
     /// Enable debug mode
     #[arg(short, long)]
     debug: bool,
+    
+    /// Maximum number of call frames shown in error backtraces
+    #[arg(long, default_value_t = 16)]
+    backtrace_depth: usize,
+    
+    /// Directory to search for modules loaded with `require`; may be given
+    /// more than once. Directories in REPLISP_PATH are searched after these.
+    #[arg(short = 'I', long = "module-path", value_name = "DIR")]
+    module_path: Vec<PathBuf>,
 }
 
 fn main() {
     let args = Args::parse();
     
+    // Evaluation recurses on the Rust stack, so it gets a thread with room
+    // for `evaluator::MAX_DEPTH` nested calls
+    let interpreter = thread::Builder::new()
+        .stack_size(evaluator::STACK_SIZE)
+        .spawn(move || run(args));
+    
+    match interpreter.map(|handle| handle.join()) {
+        Ok(Ok(())) => {}
+        Ok(Err(_)) => process::exit(101),
+        Err(err) => {
+            eprintln!("Error: cannot start interpreter thread: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+fn run(args: Args) {
+    let mut module_path = args.module_path;
+    if let Some(paths) = std::env::var_os("REPLISP_PATH") {
+        module_path.extend(std::env::split_paths(&paths));
+    }
+    
     match args.file {
         Some(filename) => {
             if args.debug {
                 println!("Loading file: {}", filename);
             }
             
-            file_exec::execute_file(&filename, args.debug);
+            file_exec::execute_file(&filename, args.debug, args.backtrace_depth, module_path);
         }
         None => {
             if args.debug {
                 println!("Starting REPL mode");
             }
             
-            repl::run_repl(args.debug);
+            repl::run_repl(args.backtrace_depth, module_path);
         }
     }
 }
\ No newline at end of file
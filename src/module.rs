@@ -0,0 +1,330 @@
+//! Modules: source files loaded with `require`. Each module is evaluated
+//! once, in a namespace of its own, and exports either the names it lists
+//! with `provide` or, failing that, everything it defines. Files can also be
+//! loaded with `load`, which evaluates them in the caller's environment.
+
+use crate::evaluator::{self, CallFrame, Environment, ErrorKind, EvalError, Value};
+use crate::span::Span;
+use crate::{parser, span, tokenizer};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+const EXTENSION: &str = "lisp";
+
+/// The bindings a module exports, by unqualified name.
+pub type Exports = Rc<Vec<(String, Value)>>;
+
+/// The modules known to a program, shared by every environment in it.
+#[derive(Debug)]
+pub struct Modules {
+    /// Directories searched for modules, after the requiring module's own
+    search_path: Vec<PathBuf>,
+    /// Modules loaded so far, by canonical path
+    loaded: HashMap<PathBuf, Exports>,
+    /// Modules being loaded, outermost first; a module that requires one of
+    /// these is part of a cycle.
+    loading: Vec<Loading>,
+}
+
+#[derive(Debug)]
+struct Loading {
+    path: PathBuf,
+    /// Where the file was found, as shown in messages
+    display: String,
+    /// Whether the file is a module being required, rather than a file
+    /// being loaded into its caller's environment
+    module: bool,
+    provided: Option<Vec<String>>,
+}
+
+impl Modules {
+    pub fn new() -> Self {
+        Self {
+            search_path: vec![PathBuf::from(".")],
+            loaded: HashMap::new(),
+            loading: Vec::new(),
+        }
+    }
+    
+    pub fn set_search_path(&mut self, search_path: Vec<PathBuf>) {
+        self.search_path = search_path;
+    }
+    
+    /// Records names exported by the module being required. Top-level code
+    /// and files being loaded have nothing to export to, so there it fails.
+    pub fn provide(&mut self, names: Vec<String>) -> Result<(), EvalError> {
+        match self.loading.last_mut() {
+            Some(loading) if loading.module => {
+                loading.provided.get_or_insert_with(Vec::new).extend(names);
+                Ok(())
+            }
+            Some(loading) => Err(module_error(format!(
+                "provide used in {}, which is loaded rather than required as a module",
+                loading.display
+            ))),
+            None => Err(module_error("provide used outside of a module".to_string())),
+        }
+    }
+    
+    /// Finds the file for a module name like `"lib/math"`, looking beside
+    /// the module that requires it first and then along the search path.
+    /// The `.lisp` extension may be left off.
+    ///
+    /// Returns the file's path along with its name for messages: the path
+    /// relative to the search path entry it was found under, so that every
+    /// module is shown the same way however it was reached.
+    fn resolve(&self, name: &str) -> Result<(PathBuf, String), EvalError> {
+        let relative = match Path::new(name).extension() {
+            Some(_) => PathBuf::from(name),
+            None => PathBuf::from(name).with_extension(EXTENSION),
+        };
+        
+        // A module beside the requiring one is shown beside that one's name
+        let requiring = self.loading.last();
+        let beside = requiring.and_then(|loading| {
+            Some((loading.path.parent()?, Path::new(&loading.display).parent()?))
+        });
+        beside
+            .into_iter()
+            .chain(self.search_path.iter().map(|dir| (dir.as_path(), Path::new(""))))
+            .map(|(dir, shown)| (dir.join(&relative), shown.join(&relative)))
+            .find(|(candidate, _)| candidate.is_file())
+            .map(|(found, shown)| (found, shown.display().to_string()))
+            .ok_or_else(|| match requiring {
+                Some(loading) => module_error(format!("cannot find module \"{}\", required by {}", name, loading.display)),
+                None => module_error(format!("cannot find module \"{}\"", name)),
+            })
+    }
+}
+
+impl Default for Modules {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Loads the module `name`, unless it has been loaded already, and returns
+/// its exports.
+pub fn require(name: &str, env: &Environment) -> Result<Exports, EvalError> {
+    let modules = env.modules();
+    let (found, display) = modules.borrow().resolve(name)?;
+    let path = fs::canonicalize(&found)
+        .map_err(|err| module_error(format!("cannot open module \"{}\": {}", name, err)))?;
+    
+    if let Some(exports) = modules.borrow().loaded.get(&path) {
+        return Ok(exports.clone());
+    }
+    
+    check_cycle(&modules.borrow(), &path, &display, "require")?;
+    
+    let source = fs::read_to_string(&path)
+        .map_err(|err| module_error(format!("cannot read module {}: {}", display, err)))?;
+    
+    // Definitions are kept apart from the builtins, so that everything the
+    // module defines can be exported
+    let namespace = env.new_namespace();
+    
+    let (result, loading) = while_loading(&modules, path.clone(), &display, true, || eval_source(&source, &display, &namespace));
+    result?;
+    
+    let exports = match loading.and_then(|loading| loading.provided) {
+        Some(names) => {
+            let mut exports = Vec::new();
+            for name in names {
+                let value = namespace.lookup(&name).ok_or_else(|| {
+                    module_error(format!("{} provides {}, which it does not define", display, name))
+                })?;
+                exports.push((name, value));
+            }
+            exports
+        }
+        None => namespace.own_bindings(),
+    };
+    
+    let exports = Rc::new(exports);
+    modules.borrow_mut().loaded.insert(path, exports.clone());
+    Ok(exports)
+}
+
+/// Evaluates the file at `path` in `env`, as though its contents had been
+/// written there, and returns the value of its last expression. Modules it
+/// requires are looked for beside it first, as they would be for a module.
+pub fn load(path: &str, env: &Environment) -> Result<Value, EvalError> {
+    let modules = env.modules();
+    let canonical = fs::canonicalize(path).map_err(|err| module_error(format!("cannot open {}: {}", path, err)))?;
+    check_cycle(&modules.borrow(), &canonical, path, "load")?;
+    
+    let source = fs::read_to_string(&canonical).map_err(|err| module_error(format!("cannot read {}: {}", path, err)))?;
+    while_loading(&modules, canonical, path, false, || eval_source(&source, path, env)).0
+}
+
+/// Fails if the file at `path` is already being loaded, since loading it
+/// again would never finish.
+fn check_cycle(modules: &Modules, path: &Path, display: &str, verb: &str) -> Result<(), EvalError> {
+    match modules.loading.iter().position(|loading| loading.path == path) {
+        Some(start) => {
+            let chain: Vec<&str> = modules.loading[start..].iter().map(|loading| loading.display.as_str()).collect();
+            Err(module_error(format!("circular {}: {} -> {}", verb, chain.join(" -> "), display)))
+        }
+        None => Ok(()),
+    }
+}
+
+/// Runs `f` with the file at `path` on top of the loading stack, and returns
+/// its result along with what the file provided.
+fn while_loading<T>(
+    modules: &RefCell<Modules>,
+    path: PathBuf,
+    display: &str,
+    module: bool,
+    f: impl FnOnce() -> T,
+) -> (T, Option<Loading>) {
+    modules.borrow_mut().loading.push(Loading {
+        path,
+        display: display.to_string(),
+        module,
+        provided: None,
+    });
+    let result = f();
+    let loading = modules.borrow_mut().loading.pop();
+    (result, loading)
+}
+
+/// Evaluates the source of a file, returning the value of its last
+/// expression. Source that can't be read is reported against the file's own
+/// text here. Evaluation errors keep their kind, so they can be caught as
+/// usual, and gain a frame for the file; their spans locate them in it.
+fn eval_source(source: &str, origin: &str, env: &Environment) -> Result<Value, EvalError> {
+    let tokens = tokenizer::tokenize_source(source, span::SourceId::register(origin, source)).map_err(|err| {
+        let message = format!("Tokenize error: {}", err);
+        failed_to_load(origin, span::format_error(origin, source, &message, Some(err.span())))
+    })?;
+    
+    let expressions = parser::parse(tokens).map_err(|err| {
+        let message = format!("Parse error: {}", err);
+        failed_to_load(origin, span::format_error(origin, source, &message, Some(err.span())))
+    })?;
+    
+    let mut result = Value::Nil;
+    for expr in &expressions {
+        result = evaluator::eval_expr(expr, env).map_err(|err| {
+            // The call site is filled in by `require` or `load`
+            err.in_frame(CallFrame {
+                function: origin.into(),
+                call_site: Span::default(),
+            })
+        })?;
+    }
+    Ok(result)
+}
+
+fn failed_to_load(origin: &str, report: String) -> EvalError {
+    module_error(format!("failed to load {}\n{}", origin, report))
+}
+
+fn module_error(message: String) -> EvalError {
+    ErrorKind::ModuleError(message).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// Writes `files` under a fresh directory, and returns an environment
+    /// whose search path is that directory along with the directory itself.
+    fn setup(test: &str, files: &[(&str, &str)]) -> (Environment, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("replisp-{}-{}", std::process::id(), test));
+        let _ = fs::remove_dir_all(&dir);
+        for (name, text) in files {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+        let env = Environment::new();
+        env.modules().borrow_mut().set_search_path(vec![dir.clone()]);
+        (env, dir)
+    }
+    
+    fn run(source: &str, env: &Environment) -> Result<Value, EvalError> {
+        let mut result = Value::Nil;
+        for expr in &parser::parse(tokenizer::tokenize(source).unwrap()).unwrap() {
+            result = evaluator::eval_expr(expr, env)?;
+        }
+        Ok(result)
+    }
+    
+    fn run_err(source: &str, env: &Environment) -> ErrorKind {
+        run(source, env).unwrap_err().kind
+    }
+    
+    const MATH: &str = "(provide square) (defn square (x) (* x x)) (defn cube (x) (* x (square x)))";
+    
+    #[test]
+    fn exports_can_be_prefixed() {
+        let (env, _) = setup("prefix", &[("lib/math.lisp", MATH)]);
+        assert_eq!(run("(require \"lib/math\" :as m) (m/square 3)", &env).unwrap().to_string(), "9");
+        assert!(matches!(run_err("square", &env), ErrorKind::UndefinedSymbol(_)));
+        assert!(matches!(run_err("m/cube", &env), ErrorKind::UndefinedSymbol(_)));
+        
+        assert_eq!(run("(require \"lib/math.lisp\") (square 4)", &env).unwrap().to_string(), "16");
+    }
+    
+    #[test]
+    fn modules_without_provide_export_everything() {
+        let (env, _) = setup("everything", &[("util.lisp", "(def a 1) (defn b () 2)")]);
+        assert_eq!(run("(require \"util\" :as u) (list u/a (u/b))", &env).unwrap().to_string(), "(1 2)");
+        assert!(matches!(run_err("u/car", &env), ErrorKind::UndefinedSymbol(_)));
+    }
+    
+    #[test]
+    fn modules_are_loaded_once() {
+        let (env, _) = setup("once", &[("state.lisp", "(def cell (box 0))")]);
+        let source = "
+            (require \"state\" :as a)
+            (require \"state\" :as b)
+            (set-box! a/cell 1)
+            (unbox b/cell)";
+        assert_eq!(run(source, &env).unwrap().to_string(), "1");
+    }
+    
+    #[test]
+    fn cycles_are_reported_with_consistent_names() {
+        let (env, _) = setup("cycle", &[
+            ("lib/a.lisp", "(require \"b\")"),
+            ("lib/b.lisp", "(require \"a\")"),
+        ]);
+        match run_err("(require \"lib/a\")", &env) {
+            ErrorKind::ModuleError(message) => {
+                assert_eq!(message, "circular require: lib/a.lisp -> lib/b.lisp -> lib/a.lisp");
+            }
+            other => panic!("expected a module error, got {:?}", other),
+        }
+        
+        // The failed modules aren't left half loaded
+        assert!(env.modules().borrow().loading.is_empty());
+        assert!(env.modules().borrow().loaded.is_empty());
+    }
+    
+    #[test]
+    fn errors_in_modules_keep_their_kind() {
+        let (env, _) = setup("kinds", &[("bad.lisp", "(defn f (x) (car x)) (f 1)")]);
+        let source = "(try (require \"bad\") (catch type-error e \"caught\"))";
+        assert_eq!(run(source, &env).unwrap().to_string(), "\"caught\"");
+        
+        let err = run("(require \"bad\")", &env).unwrap_err();
+        let functions: Vec<&str> = err.backtrace.iter().map(|frame| &*frame.function).collect();
+        assert_eq!(functions, ["f", "bad.lisp"]);
+    }
+    
+    #[test]
+    fn provide_outside_a_module_fails() {
+        let (env, dir) = setup("provide", &[("loaded.lisp", "(provide x) (def x 1)")]);
+        assert!(matches!(run_err("(provide x)", &env), ErrorKind::ModuleError(_)));
+        
+        let source = format!("(load \"{}\")", dir.join("loaded.lisp").display());
+        assert!(matches!(run_err(&source, &env), ErrorKind::ModuleError(_)));
+    }
+}
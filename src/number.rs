@@ -0,0 +1,469 @@
+//! The numeric tower: exact integers, which grow from `i64` into bignums as
+//! needed, exact rationals, and floats. Arithmetic on exact numbers stays
+//! exact; mixing in a float makes the result a float.
+
+use crate::bigint::BigInt;
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use std::ops::{Add, Mul, Neg, Sub};
+
+#[derive(Debug, Clone)]
+pub enum Number {
+    Int(i64),
+    /// An integer outside the range of `Int`
+    Big(BigInt),
+    /// A fraction in lowest terms, with a denominator greater than 1. The
+    /// parts are boxed so that the common cases keep `Number` small.
+    Ratio(Box<BigInt>, Box<BigInt>),
+    Float(f64),
+}
+
+/// A pair of operands brought to a common representation: both floats, or
+/// both exact as numerator and denominator.
+enum Operands {
+    Floats(f64, f64),
+    Exact((BigInt, BigInt), (BigInt, BigInt)),
+}
+
+impl Number {
+    /// Builds the fraction `numerator/denominator`, reduced to lowest terms,
+    /// or an integer if it divides evenly. `denominator` must not be zero.
+    pub fn ratio(numerator: BigInt, denominator: BigInt) -> Number {
+        let (numerator, denominator) = if denominator.is_negative() {
+            (-&numerator, -&denominator)
+        } else {
+            (numerator, denominator)
+        };
+        
+        if denominator == BigInt::from(1) {
+            return Number::from(numerator);
+        }
+        
+        let gcd = numerator.gcd(&denominator);
+        let (numerator, _) = numerator.div_rem(&gcd);
+        let (denominator, _) = denominator.div_rem(&gcd);
+        
+        if denominator == BigInt::from(1) {
+            Number::from(numerator)
+        } else {
+            Number::Ratio(Box::new(numerator), Box::new(denominator))
+        }
+    }
+    
+    pub fn is_zero(&self) -> bool {
+        match self {
+            Number::Int(n) => *n == 0,
+            Number::Float(f) => *f == 0.0,
+            // Bignums and ratios are never zero once normalized
+            Number::Big(_) | Number::Ratio(..) => false,
+        }
+    }
+    
+    pub fn is_negative(&self) -> bool {
+        match self {
+            Number::Int(n) => *n < 0,
+            Number::Big(n) => n.is_negative(),
+            Number::Ratio(n, _) => n.is_negative(),
+            Number::Float(f) => *f < 0.0,
+        }
+    }
+    
+    pub fn is_exact(&self) -> bool {
+        !matches!(self, Number::Float(_))
+    }
+    
+    pub fn is_integer(&self) -> bool {
+        matches!(self, Number::Int(_) | Number::Big(_))
+    }
+    
+    /// Returns the value as an `i64` if it is an integer that fits.
+    pub fn to_i64(&self) -> Option<i64> {
+        match self {
+            Number::Int(n) => Some(*n),
+            _ => None,
+        }
+    }
+    
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Number::Int(n) => *n as f64,
+            Number::Big(n) => n.to_f64(),
+            Number::Ratio(n, d) => n.to_f64() / d.to_f64(),
+            Number::Float(f) => *f,
+        }
+    }
+    
+    fn to_integer(&self) -> Option<BigInt> {
+        match self {
+            Number::Int(n) => Some(BigInt::from(*n)),
+            Number::Big(n) => Some(n.clone()),
+            _ => None,
+        }
+    }
+    
+    /// The numerator and denominator of an exact number.
+    fn to_fraction(&self) -> Option<(BigInt, BigInt)> {
+        match self {
+            Number::Int(n) => Some((BigInt::from(*n), BigInt::from(1))),
+            Number::Big(n) => Some((n.clone(), BigInt::from(1))),
+            Number::Ratio(n, d) => Some((BigInt::clone(n), BigInt::clone(d))),
+            Number::Float(_) => None,
+        }
+    }
+    
+    /// The numerator and denominator of the number's exact value; for a
+    /// float, the fraction it stands for. Returns `None` for infinities and
+    /// NaN.
+    fn to_exact_fraction(&self) -> Option<(BigInt, BigInt)> {
+        match self {
+            Number::Float(f) => exact_float(*f)?.to_fraction(),
+            _ => self.to_fraction(),
+        }
+    }
+    
+    fn coerce(&self, other: &Number) -> Operands {
+        match (self.to_fraction(), other.to_fraction()) {
+            (Some(a), Some(b)) => Operands::Exact(a, b),
+            _ => Operands::Floats(self.to_f64(), other.to_f64()),
+        }
+    }
+    
+    pub fn abs(&self) -> Number {
+        if self.is_negative() { -self } else { self.clone() }
+    }
+    
+    /// Divides, keeping exact operands exact. Returns `None` when dividing
+    /// an exact number by exact zero; with a float involved, division
+    /// follows IEEE rules instead, so `(/ 1 0.0)` is infinite.
+    pub fn checked_div(&self, other: &Number) -> Option<Number> {
+        match self.coerce(other) {
+            Operands::Floats(a, b) => Some(Number::Float(a / b)),
+            Operands::Exact(..) if other.is_zero() => None,
+            Operands::Exact((an, ad), (bn, bd)) => Some(Number::ratio(&an * &bd, &ad * &bn)),
+        }
+    }
+    
+    /// The remainder of dividing with the quotient rounded towards zero, so
+    /// it has the sign of `self`. Like `checked_div`, returns `None` only
+    /// for exact division by zero.
+    pub fn checked_rem(&self, other: &Number) -> Option<Number> {
+        if let (Number::Int(a), Number::Int(b)) = (self, other)
+            && let Some(remainder) = a.checked_rem(*b)
+        {
+            return Some(Number::Int(remainder));
+        }
+        
+        match self.coerce(other) {
+            Operands::Floats(a, b) => Some(Number::Float(a % b)),
+            Operands::Exact(..) if other.is_zero() => None,
+            Operands::Exact((an, ad), (bn, bd)) => Some({
+                // a - b * trunc(a / b), over the common denominator ad * bd
+                let (quotient, _) = (&an * &bd).div_rem(&(&ad * &bn));
+                let numerator = &(&an * &bd) - &(&(&quotient * &bn) * &ad);
+                Number::ratio(numerator, &ad * &bd)
+            }),
+        }
+    }
+}
+
+/// The exact value of a finite float, a fraction whose denominator is a power
+/// of two. Returns `None` for infinities and NaN.
+fn exact_float(f: f64) -> Option<Number> {
+    if !f.is_finite() {
+        return None;
+    }
+    
+    let bits = f.to_bits();
+    let exponent = ((bits >> 52) & 0x7ff) as i64;
+    let fraction = (bits & ((1 << 52) - 1)) as i64;
+    // Subnormal floats have no implicit leading bit
+    let (mantissa, exponent) = match exponent {
+        0 => (fraction, -1074),
+        _ => (fraction | 1 << 52, exponent - 1075),
+    };
+    
+    let mantissa = BigInt::from(if f.is_sign_negative() { -mantissa } else { mantissa });
+    let scale = power_of_two(exponent.unsigned_abs());
+    Some(if exponent < 0 {
+        Number::ratio(mantissa, scale)
+    } else {
+        Number::from(&mantissa * &scale)
+    })
+}
+
+fn power_of_two(exponent: u64) -> BigInt {
+    let mut result = BigInt::from(1);
+    let mut remaining = exponent;
+    while remaining > 0 {
+        let step = remaining.min(62);
+        result = &result * &BigInt::from(1 << step);
+        remaining -= step;
+    }
+    result
+}
+
+/// The largest magnitude up to which every integer is exactly a float.
+const EXACT_FLOAT_INTEGERS: u64 = 1 << 53;
+
+impl From<i64> for Number {
+    fn from(n: i64) -> Self {
+        Number::Int(n)
+    }
+}
+
+impl From<BigInt> for Number {
+    fn from(n: BigInt) -> Self {
+        match n.to_i64() {
+            Some(n) => Number::Int(n),
+            None => Number::Big(n),
+        }
+    }
+}
+
+impl Add for &Number {
+    type Output = Number;
+    
+    fn add(self, other: &Number) -> Number {
+        if let (Number::Int(a), Number::Int(b)) = (self, other)
+            && let Some(sum) = a.checked_add(*b)
+        {
+            return Number::Int(sum);
+        }
+        
+        if let (Some(a), Some(b)) = (self.to_integer(), other.to_integer()) {
+            return Number::from(&a + &b);
+        }
+        
+        match self.coerce(other) {
+            Operands::Floats(a, b) => Number::Float(a + b),
+            Operands::Exact((an, ad), (bn, bd)) => Number::ratio(&(&an * &bd) + &(&bn * &ad), &ad * &bd),
+        }
+    }
+}
+
+impl Sub for &Number {
+    type Output = Number;
+    
+    fn sub(self, other: &Number) -> Number {
+        if let (Number::Int(a), Number::Int(b)) = (self, other)
+            && let Some(difference) = a.checked_sub(*b)
+        {
+            return Number::Int(difference);
+        }
+        
+        self + &-other
+    }
+}
+
+impl Mul for &Number {
+    type Output = Number;
+    
+    fn mul(self, other: &Number) -> Number {
+        if let (Number::Int(a), Number::Int(b)) = (self, other)
+            && let Some(product) = a.checked_mul(*b)
+        {
+            return Number::Int(product);
+        }
+        
+        if let (Some(a), Some(b)) = (self.to_integer(), other.to_integer()) {
+            return Number::from(&a * &b);
+        }
+        
+        match self.coerce(other) {
+            Operands::Floats(a, b) => Number::Float(a * b),
+            Operands::Exact((an, ad), (bn, bd)) => Number::ratio(&an * &bn, &ad * &bd),
+        }
+    }
+}
+
+impl Neg for &Number {
+    type Output = Number;
+    
+    fn neg(self) -> Number {
+        match self {
+            Number::Int(n) => match n.checked_neg() {
+                Some(n) => Number::Int(n),
+                None => Number::from(-&BigInt::from(*n)),
+            },
+            Number::Big(n) => Number::from(-n),
+            Number::Ratio(n, d) => Number::Ratio(Box::new(-&**n), d.clone()),
+            Number::Float(f) => Number::Float(-f),
+        }
+    }
+}
+
+/// Numbers compare by value, so `1`, `1.0` and `2/2` are all equal. A float
+/// is compared with an exact number as the fraction it stands for, rather
+/// than by rounding the exact number to a float: that would make
+/// 9007199254740993 equal 9007199254740992.0, which is not itself equal to
+/// 9007199254740993, and equality would stop being transitive.
+impl PartialEq for Number {
+    fn eq(&self, other: &Self) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Number::Int(a), Number::Int(b)) => return Some(a.cmp(b)),
+            (Number::Float(a), Number::Float(b)) => return a.partial_cmp(b),
+            // Small integers are exactly floats, so need no fractions
+            (Number::Int(a), Number::Float(b)) if a.unsigned_abs() <= EXACT_FLOAT_INTEGERS => {
+                return (*a as f64).partial_cmp(b);
+            }
+            (Number::Float(a), Number::Int(b)) if b.unsigned_abs() <= EXACT_FLOAT_INTEGERS => {
+                return a.partial_cmp(&(*b as f64));
+            }
+            _ => {}
+        }
+        
+        match (self.to_exact_fraction(), other.to_exact_fraction()) {
+            // Denominators are positive, so cross-multiplying keeps the order
+            (Some((an, ad)), Some((bn, bd))) => Some((&an * &bd).cmp(&(&bn * &ad))),
+            // One side is an infinity or NaN, and the other finite
+            (Some(_), None) => other.to_f64().partial_cmp(&0.0).map(Ordering::reverse),
+            (None, _) => self.to_f64().partial_cmp(&0.0),
+        }
+    }
+}
+
+/// Hashes agree with equality: a finite float hashes as the exact number it
+/// stands for, so `1`, `1.0` and `2/2` land in the same bucket.
+impl Hash for Number {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Number::Int(n) => n.hash(state),
+            Number::Big(n) => n.hash(state),
+            Number::Ratio(n, d) => {
+                n.hash(state);
+                d.hash(state);
+            }
+            // Integral floats in range are hashed directly, being the common
+            // case; this includes -0.0, which equals 0
+            Number::Float(f) if f.fract() == 0.0 && f.abs() < i64::MAX as f64 => (*f as i64).hash(state),
+            Number::Float(f) => match exact_float(*f) {
+                Some(exact) => exact.hash(state),
+                None => f.to_bits().hash(state),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Number::Int(n) => write!(f, "{}", n),
+            Number::Big(n) => write!(f, "{}", n),
+            Number::Ratio(n, d) => write!(f, "{}/{}", n, d),
+            Number::Float(x) if x.is_nan() => write!(f, "nan"),
+            Number::Float(x) if x.is_infinite() => write!(f, "{}inf", if *x < 0.0 { "-" } else { "" }),
+            // Debug formatting always includes a decimal point or exponent,
+            // so floats stay distinguishable from integers
+            Number::Float(x) => write!(f, "{:?}", x),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn ratio(numerator: i64, denominator: i64) -> Number {
+        Number::ratio(BigInt::from(numerator), BigInt::from(denominator))
+    }
+    
+    #[test]
+    fn ratio_moves_a_negative_denominator_to_the_numerator() {
+        assert_eq!(ratio(3, -6).to_string(), "-1/2");
+        assert_eq!(ratio(-3, -6).to_string(), "1/2");
+        assert!(matches!(ratio(-4, -2), Number::Int(2)));
+        assert!(matches!(ratio(0, -5), Number::Int(0)));
+    }
+    
+    #[test]
+    fn checked_rem_on_ratios() {
+        // 7/2 = 10 * 1/3 + 1/6
+        assert_eq!(ratio(7, 2).checked_rem(&ratio(1, 3)).unwrap().to_string(), "1/6");
+        assert_eq!(ratio(-7, 2).checked_rem(&ratio(1, 3)).unwrap().to_string(), "-1/6");
+        assert_eq!(ratio(7, 2).checked_rem(&ratio(-1, 3)).unwrap().to_string(), "1/6");
+        assert!(matches!(ratio(3, 2).checked_rem(&ratio(1, 2)), Some(Number::Int(0))));
+        assert!(ratio(1, 2).checked_rem(&Number::Int(0)).is_none());
+    }
+    
+    #[test]
+    fn only_exact_division_by_zero_fails() {
+        let float = |f: f64| Number::Float(f);
+        assert!(Number::Int(1).checked_div(&Number::Int(0)).is_none());
+        assert!(ratio(1, 2).checked_rem(&Number::Int(0)).is_none());
+        
+        assert_eq!(float(1.0).checked_div(&Number::Int(0)).unwrap().to_f64(), f64::INFINITY);
+        assert_eq!(Number::Int(-1).checked_div(&float(0.0)).unwrap().to_f64(), f64::NEG_INFINITY);
+        assert!(float(0.0).checked_div(&Number::Int(0)).unwrap().to_f64().is_nan());
+        assert!(Number::Int(5).checked_rem(&float(0.0)).unwrap().to_f64().is_nan());
+    }
+    
+    fn hash(number: &Number) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        number.hash(&mut hasher);
+        hasher.finish()
+    }
+    
+    #[test]
+    fn floats_compare_exactly_with_exact_numbers() {
+        let above = Number::Int((1 << 53) + 1);
+        let float = Number::Float((1u64 << 53) as f64);
+        assert_ne!(above, float);
+        assert!(above > float);
+        assert_eq!(Number::Int(1 << 53), float);
+        
+        let big: Number = "18446744073709551616".parse::<BigInt>().unwrap().into();
+        assert_eq!(big, Number::Float(18446744073709551616.0));
+        assert!(&big + &Number::Int(1) > Number::Float(18446744073709551616.0));
+        
+        // 1/3 isn't a float, and the nearest one is a little less
+        assert!(ratio(1, 3) > Number::Float(1.0 / 3.0));
+        assert_eq!(ratio(-1, 2), Number::Float(-0.5));
+    }
+    
+    #[test]
+    fn exact_numbers_compare_with_infinities_and_nan() {
+        let big = Number::from(power_of_two(2000));
+        assert!(big < Number::Float(f64::INFINITY));
+        assert!(-&big > Number::Float(f64::NEG_INFINITY));
+        assert!(Number::Float(f64::INFINITY) > big);
+        assert_eq!(ratio(1, 2).partial_cmp(&Number::Float(f64::NAN)), None);
+        assert_eq!(Number::Float(f64::NAN).partial_cmp(&Number::Int(1)), None);
+    }
+    
+    #[test]
+    fn equal_numbers_hash_alike() {
+        let smallest = Number::ratio(BigInt::from(1), power_of_two(1074));
+        let pairs = [
+            (Number::Int(1), Number::Float(1.0)),
+            (Number::Int(0), Number::Float(-0.0)),
+            (ratio(3, 4), Number::Float(0.75)),
+            (Number::from(power_of_two(70)), Number::Float(2f64.powi(70))),
+            (smallest, Number::Float(f64::from_bits(1))),
+        ];
+        for (exact, float) in &pairs {
+            assert_eq!(exact, float);
+            assert_eq!(hash(exact), hash(float), "{} and {}", exact, float);
+        }
+    }
+    
+    #[test]
+    fn negating_i64_min_overflows_into_a_bignum() {
+        let min = Number::Int(i64::MIN);
+        let negated = -&min;
+        assert!(matches!(negated, Number::Big(_)));
+        assert_eq!(negated.to_string(), "9223372036854775808");
+        assert!(matches!(-&negated, Number::Int(i64::MIN)));
+        assert_eq!(min.abs().to_string(), "9223372036854775808");
+    }
+    
+    #[test]
+    fn checked_rem_of_i64_min_by_minus_one() {
+        assert!(matches!(Number::Int(i64::MIN).checked_rem(&Number::Int(-1)), Some(Number::Int(0))));
+    }
+}
@@ -1,28 +1,78 @@
-use crate::tokenizer::Token;
+use crate::evaluator::{EvalError, Value};
+use crate::keyword::Keyword;
+use crate::number::Number;
+use crate::span::Span;
+use crate::tokenizer::{char_literal, escape_string, SpannedToken, Token};
+use std::rc::Rc;
+
+/// A parsed expression and the region of source it came from.
+#[derive(Debug, Clone)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
 
 #[derive(Debug, Clone, PartialEq)]
-pub enum Expr {
-    Number(f64),
+pub enum ExprKind {
+    Number(Number),
     String(String),
+    Char(char),
     Symbol(String),
-    List(Vec<Expr>),
+    Keyword(Keyword),
+    // Elements are shared so that subexpressions can be cloned cheaply
+    List(Rc<[Expr]>),
+    Vector(Rc<[Expr]>),
+    /// Keys and values alternate, as written
+    Map(Rc<[Expr]>),
+    Set(Rc<[Expr]>),
     Quote(Box<Expr>),
+    Quasiquote(Box<Expr>),
+    Unquote(Box<Expr>),
+    UnquoteSplicing(Box<Expr>),
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+    
+    /// Converts data to the code that reads as it; see `Value::to_expr`.
+    pub fn from_value(value: &Value) -> Result<Self, EvalError> {
+        value.to_expr()
+    }
+    
+    /// Converts code to the data it reads as; see `Value::from_expr`.
+    pub fn to_value(&self) -> Value {
+        Value::from_expr(self)
+    }
+}
+
+// Spans are ignored, so code built at runtime compares equal to the same code
+// read from source.
+impl PartialEq for Expr {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+    }
 }
 
 pub struct Parser {
-    tokens: Vec<Token>,
+    tokens: Vec<SpannedToken>,
     position: usize,
 }
 
 #[derive(Debug)]
 pub enum ParseError {
-    UnexpectedEof,
-    UnexpectedToken(Token),
-    UnmatchedParen,
+    UnexpectedEof(Span),
+    UnexpectedToken(Token, Span),
+    /// A list or collection was never closed; the span is that of its
+    /// opening delimiter.
+    UnmatchedDelimiter(char, Span),
+    /// A map literal with a key that has no value.
+    OddMapLiteral(Span),
 }
 
 impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
+    pub fn new(tokens: Vec<SpannedToken>) -> Self {
         Self {
             tokens,
             position: 0,
@@ -30,7 +80,21 @@ impl Parser {
     }
     
     fn current_token(&self) -> Option<&Token> {
-        self.tokens.get(self.position)
+        self.tokens.get(self.position).map(|t| &t.token)
+    }
+    
+    fn current_span(&self) -> Span {
+        match self.tokens.get(self.position).or(self.tokens.last()) {
+            Some(token) => token.span,
+            None => Span::default(),
+        }
+    }
+    
+    fn previous_span(&self) -> Span {
+        match self.position.checked_sub(1).and_then(|i| self.tokens.get(i)) {
+            Some(token) => token.span,
+            None => Span::default(),
+        }
     }
     
     fn advance(&mut self) {
@@ -65,53 +129,110 @@ impl Parser {
     }
     
     fn parse_expression(&mut self) -> Result<Expr, ParseError> {
+        let span = self.current_span();
+        
         match self.current_token() {
             Some(Token::Number(n)) => {
-                let num = *n;
+                let num = n.clone();
                 self.advance();
-                Ok(Expr::Number(num))
+                Ok(Expr::new(ExprKind::Number(num), span))
             }
             
             Some(Token::String(s)) => {
                 let string = s.clone();
                 self.advance();
-                Ok(Expr::String(string))
+                Ok(Expr::new(ExprKind::String(string), span))
+            }
+            
+            Some(Token::Char(ch)) => {
+                let ch = *ch;
+                self.advance();
+                Ok(Expr::new(ExprKind::Char(ch), span))
             }
             
             Some(Token::Symbol(s)) => {
                 let symbol = s.clone();
                 self.advance();
-                Ok(Expr::Symbol(symbol))
+                Ok(Expr::new(ExprKind::Symbol(symbol), span))
+            }
+            
+            Some(Token::Keyword(name)) => {
+                let keyword = Keyword::new(name);
+                self.advance();
+                Ok(Expr::new(ExprKind::Keyword(keyword), span))
             }
             
             Some(Token::Quote) => {
                 self.advance();
                 let expr = self.parse_expression()?;
-                Ok(Expr::Quote(Box::new(expr)))
+                Ok(Expr::new(ExprKind::Quote(Box::new(expr)), span.to(self.previous_span())))
+            }
+            
+            Some(Token::Quasiquote) => {
+                self.advance();
+                let expr = self.parse_expression()?;
+                Ok(Expr::new(ExprKind::Quasiquote(Box::new(expr)), span.to(self.previous_span())))
+            }
+            
+            Some(Token::Unquote) => {
+                self.advance();
+                let expr = self.parse_expression()?;
+                Ok(Expr::new(ExprKind::Unquote(Box::new(expr)), span.to(self.previous_span())))
+            }
+            
+            Some(Token::UnquoteSplicing) => {
+                self.advance();
+                let expr = self.parse_expression()?;
+                Ok(Expr::new(ExprKind::UnquoteSplicing(Box::new(expr)), span.to(self.previous_span())))
             }
             
             Some(Token::LeftParen) => {
                 self.advance();
-                self.parse_list()
+                let elements = self.parse_sequence('(', Token::RightParen, span)?;
+                Ok(Expr::new(ExprKind::List(elements.into()), span.to(self.previous_span())))
+            }
+            
+            Some(Token::LeftBracket) => {
+                self.advance();
+                let elements = self.parse_sequence('[', Token::RightBracket, span)?;
+                Ok(Expr::new(ExprKind::Vector(elements.into()), span.to(self.previous_span())))
+            }
+            
+            Some(Token::LeftBrace) => {
+                self.advance();
+                let elements = self.parse_sequence('{', Token::RightBrace, span)?;
+                let span = span.to(self.previous_span());
+                if elements.len() % 2 != 0 {
+                    return Err(ParseError::OddMapLiteral(span));
+                }
+                Ok(Expr::new(ExprKind::Map(elements.into()), span))
+            }
+            
+            Some(Token::HashBrace) => {
+                self.advance();
+                let elements = self.parse_sequence('{', Token::RightBrace, span)?;
+                Ok(Expr::new(ExprKind::Set(elements.into()), span.to(self.previous_span())))
             }
             
-            Some(token) => Err(ParseError::UnexpectedToken(token.clone())),
-            None => Err(ParseError::UnexpectedEof),
+            Some(Token::Eof) | None => Err(ParseError::UnexpectedEof(span)),
+            Some(token) => Err(ParseError::UnexpectedToken(token.clone(), span)),
         }
     }
     
-    fn parse_list(&mut self) -> Result<Expr, ParseError> {
+    /// Parses the elements of a list or collection up to and including the
+    /// `close` token. The opening delimiter has already been consumed.
+    fn parse_sequence(&mut self, open: char, close: Token, open_span: Span) -> Result<Vec<Expr>, ParseError> {
         let mut elements = Vec::new();
         
         loop {
             match self.current_token() {
-                Some(Token::RightParen) => {
+                Some(token) if *token == close => {
                     self.advance();
                     break;
                 }
                 
                 Some(Token::Eof) => {
-                    return Err(ParseError::UnmatchedParen);
+                    return Err(ParseError::UnmatchedDelimiter(open, open_span));
                 }
                 
                 Some(_) => {
@@ -120,26 +241,45 @@ impl Parser {
                 }
                 
                 None => {
-                    return Err(ParseError::UnexpectedEof);
+                    return Err(ParseError::UnexpectedEof(self.current_span()));
                 }
             }
         }
         
-        Ok(Expr::List(elements))
+        Ok(elements)
     }
 }
 
-pub fn parse(tokens: Vec<Token>) -> Result<Vec<Expr>, ParseError> {
+pub fn parse(tokens: Vec<SpannedToken>) -> Result<Vec<Expr>, ParseError> {
     let mut parser = Parser::new(tokens);
     parser.parse()
 }
 
+impl ParseError {
+    /// Whether more input could complete the source, as with an open list.
+    pub fn is_incomplete(&self) -> bool {
+        matches!(self, ParseError::UnexpectedEof(_) | ParseError::UnmatchedDelimiter(..))
+    }
+    
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::UnexpectedEof(span) => *span,
+            ParseError::UnexpectedToken(_, span) => *span,
+            ParseError::UnmatchedDelimiter(_, span) => *span,
+            ParseError::OddMapLiteral(span) => *span,
+        }
+    }
+}
+
 impl std::fmt::Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ParseError::UnexpectedEof => write!(f, "Unexpected end of input"),
-            ParseError::UnexpectedToken(token) => write!(f, "Unexpected token: {:?}", token),
-            ParseError::UnmatchedParen => write!(f, "Unmatched parenthesis"),
+            ParseError::UnexpectedEof(_) => write!(f, "Unexpected end of input"),
+            ParseError::UnexpectedToken(token, _) => write!(f, "Unexpected token: {:?}", token),
+            ParseError::UnmatchedDelimiter('(', _) => write!(f, "Unmatched parenthesis"),
+            ParseError::UnmatchedDelimiter('[', _) => write!(f, "Unmatched bracket"),
+            ParseError::UnmatchedDelimiter(_, _) => write!(f, "Unmatched brace"),
+            ParseError::OddMapLiteral(_) => write!(f, "Map literal requires an even number of forms"),
         }
     }
 }
@@ -149,21 +289,31 @@ impl std::error::Error for ParseError {}
 // Pretty printing for expressions
 impl std::fmt::Display for Expr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Expr::Number(n) => write!(f, "{}", n),
-            Expr::String(s) => write!(f, "\"{}\"", s),
-            Expr::Symbol(s) => write!(f, "{}", s),
-            Expr::Quote(expr) => write!(f, "'{}", expr),
-            Expr::List(elements) => {
-                write!(f, "(")?;
-                for (i, elem) in elements.iter().enumerate() {
-                    if i > 0 {
-                        write!(f, " ")?;
-                    }
-                    write!(f, "{}", elem)?;
-                }
-                write!(f, ")")
-            }
+        match &self.kind {
+            ExprKind::Number(n) => write!(f, "{}", n),
+            ExprKind::String(s) => write!(f, "\"{}\"", escape_string(s)),
+            ExprKind::Symbol(s) => write!(f, "{}", s),
+            ExprKind::Keyword(k) => write!(f, "{}", k),
+            ExprKind::Char(ch) => write!(f, "{}", char_literal(*ch)),
+            ExprKind::Quote(expr) => write!(f, "'{}", expr),
+            ExprKind::Quasiquote(expr) => write!(f, "`{}", expr),
+            ExprKind::Unquote(expr) => write!(f, ",{}", expr),
+            ExprKind::UnquoteSplicing(expr) => write!(f, ",@{}", expr),
+            ExprKind::List(elements) => write_sequence(f, "(", elements, ")"),
+            ExprKind::Vector(elements) => write_sequence(f, "[", elements, "]"),
+            ExprKind::Map(elements) => write_sequence(f, "{", elements, "}"),
+            ExprKind::Set(elements) => write_sequence(f, "#{", elements, "}"),
         }
     }
-}
\ No newline at end of file
+}
+
+fn write_sequence(f: &mut std::fmt::Formatter<'_>, open: &str, elements: &[Expr], close: &str) -> std::fmt::Result {
+    write!(f, "{}", open)?;
+    for (i, elem) in elements.iter().enumerate() {
+        if i > 0 {
+            write!(f, " ")?;
+        }
+        write!(f, "{}", elem)?;
+    }
+    write!(f, "{}", close)
+}
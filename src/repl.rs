@@ -1,82 +1,123 @@
-use std::io::{self, Write};
-use crate::{tokenizer, parser, evaluator};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Instant;
+use crate::{tokenizer, parser, evaluator, span};
+use crate::evaluator::{Environment, Value};
+use crate::line_editor::{LineEditor, ReadLine};
+use crate::parser::{Expr, ExprKind};
 
-pub fn run_repl(debug: bool) {
+const PROMPT: &str = "replisp> ";
+const CONTINUATION_PROMPT: &str = "     ... ";
+
+/// Where input history is kept, relative to the home directory.
+const HISTORY_FILE: &str = ".replisp_history";
+
+enum Flow {
+    Continue,
+    Quit,
+}
+
+/// A REPL command, entered as `:name` followed by its argument.
+struct Command {
+    name: &'static str,
+    alias: Option<&'static str>,
+    /// Describes the argument, or is empty if the command takes none.
+    usage: &'static str,
+    help: &'static str,
+    run: fn(&mut Session, &str) -> Result<Flow, String>,
+}
+
+const COMMANDS: &[Command] = &[
+    Command { name: "help", alias: Some("h"), usage: "", help: "Show this list of commands", run: command_help },
+    Command { name: "quit", alias: Some("q"), usage: "", help: "Exit the REPL", run: command_quit },
+    Command { name: "env", alias: None, usage: "", help: "List the bindings in the environment", run: command_env },
+    Command { name: "doc", alias: None, usage: "<symbol>", help: "Describe what a symbol is bound to", run: command_doc },
+    Command { name: "load", alias: None, usage: "<file>", help: "Evaluate a file in this session", run: command_load },
+    Command { name: "save", alias: None, usage: "<file>", help: "Write this session's definitions to a file", run: command_save },
+    Command { name: "reset", alias: None, usage: "", help: "Discard all definitions and start afresh", run: command_reset },
+    Command { name: "time", alias: None, usage: "<expr>", help: "Evaluate an expression and show how long it took", run: command_time },
+    Command { name: "type", alias: None, usage: "<expr>", help: "Show the type of an expression's value", run: command_type },
+    Command { name: "tokens", alias: None, usage: "<expr>", help: "Show the tokens an expression is read as", run: command_tokens },
+    Command { name: "ast", alias: None, usage: "<expr>", help: "Show the syntax tree an expression is parsed into", run: command_ast },
+];
+
+/// The state of a REPL session.
+struct Session {
+    env: Environment,
+    /// The source of each top-level definition made so far, oldest first,
+    /// kept so that `:save` can write them out again.
+    definitions: Vec<(String, String)>,
+    backtrace_depth: usize,
+    /// Directories searched for modules, after the current directory
+    module_path: Vec<PathBuf>,
+}
+
+pub fn run_repl(backtrace_depth: usize, module_path: Vec<PathBuf>) {
     println!("Welcome to REPLisp!");
     println!("Type expressions to evaluate them.");
-    println!("Type :quit or press Ctrl+C to exit.");
+    println!("Type :help for a list of commands, or :quit to exit.");
+    
+    let mut session = Session {
+        env: new_environment(&module_path),
+        definitions: Vec::new(),
+        backtrace_depth,
+        module_path,
+    };
+    let history_path = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE));
+    let mut editor = LineEditor::new(history_path);
     
-    let mut env = evaluator::Environment::new();
+    // Lines are collected here until they form complete expressions
+    let mut input = String::new();
     
     loop {
-        print!("replisp> ");
-        io::stdout().flush().unwrap();
+        let prompt = if input.is_empty() { PROMPT } else { CONTINUATION_PROMPT };
+        let line = editor.read_line(prompt, &|word| session.complete(word));
         
-        let mut input = String::new();
-        match io::stdin().read_line(&mut input) {
-            Ok(0) => break, // EOF
-            Ok(_) => {
-                let input = input.trim();
+        match line {
+            Ok(ReadLine::Eof) => break,
+            Ok(ReadLine::Interrupted) => {
+                // Ctrl+C abandons any unfinished expression
+                input.clear();
+            }
+            Ok(ReadLine::Line(line)) => {
+                editor.add_history(&line);
                 
                 if input.is_empty() {
-                    continue;
-                }
-                
-                if input == ":quit" || input == ":q" {
-                    println!("Goodbye!");
-                    break;
-                }
-                
-                if debug {
-                    println!("Input: {}", input);
+                    let trimmed = line.trim();
+                    
+                    if trimmed.is_empty() {
+                        continue;
+                    }
+                    
+                    // Anything else starting with a colon is a keyword
+                    if let Some((command, arg)) = parse_command(trimmed) {
+                        match session.run_command(command, arg) {
+                            Flow::Continue => continue,
+                            Flow::Quit => break,
+                        }
+                    }
                 }
                 
-                let tokens = tokenizer::tokenize(input);
-
-                if tokens.is_none() {
-                    eprintln!("Error tokenizing input: {}", input);
-                    continue;
-                }
-                let tokens = tokens.unwrap();
+                input.push_str(&line);
+                input.push('\n');
                 
-                if debug {
-                    println!("Tokens ({} total):", tokens.len());
-                    for (i, token) in tokens.iter().enumerate() {
-                        println!("  {}: {:?}", i, token);
+                let expressions = match read(&input) {
+                    Ok(Some(expressions)) => expressions,
+                    Ok(None) => continue, // Wait for the rest of the input
+                    Err(message) => {
+                        eprintln!("{}", message);
+                        input.clear();
+                        continue;
                     }
-                    println!("---");
-                }
+                };
                 
-                match parser::parse(tokens) {
-                    Ok(expressions) => {
-                        if debug {
-                            println!("AST ({} expressions):", expressions.len());
-                            for (i, expr) in expressions.iter().enumerate() {
-                                println!("  {}: {:?}", i, expr);
-                            }
-                            println!("---");
-                        }
-                        
-                        // Evaluate each expression
-                        for expr in &expressions {
-                            match evaluator::eval_expr(expr, &mut env) {
-                                Ok(value) => {
-                                    if debug {
-                                        println!("Result: {}", value);
-                                    } else {
-                                        println!("{}", value);
-                                    }
-                                }
-                                Err(err) => {
-                                    eprintln!("Evaluation error: {}", err);
-                                }
-                            }
-                        }
-                    }
-                    Err(err) => {
-                        eprintln!("Parse error: {}", err);
+                for expr in &expressions {
+                    if let Some(value) = session.eval(expr, &input, "<repl>") {
+                        println!("{}", value);
                     }
                 }
+                
+                input.clear();
             }
             Err(err) => {
                 eprintln!("Error reading input: {}", err);
@@ -84,4 +125,344 @@ pub fn run_repl(debug: bool) {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// Creates a global environment that finds modules in the current directory
+/// and then `module_path`.
+fn new_environment(module_path: &[PathBuf]) -> Environment {
+    let env = Environment::new();
+    let search_path = std::iter::once(PathBuf::from(".")).chain(module_path.iter().cloned()).collect();
+    env.modules().borrow_mut().set_search_path(search_path);
+    env
+}
+
+/// Splits a line like `:doc map` into its command and argument. Returns
+/// `None` if the line doesn't start with the name of a command.
+fn parse_command(line: &str) -> Option<(&'static Command, &str)> {
+    let line = line.strip_prefix(':')?;
+    let (name, arg) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let command = COMMANDS.iter().find(|c| c.name == name || c.alias == Some(name))?;
+    Some((command, arg.trim()))
+}
+
+/// Tokenizes and parses REPL input. Returns `Ok(None)` if it ends partway
+/// through an expression, such as inside an unclosed list or string, and a
+/// formatted error message if it can't be parsed at all.
+fn read(input: &str) -> Result<Option<Vec<Expr>>, String> {
+    let tokens = match tokenizer::tokenize(input) {
+        Ok(tokens) => tokens,
+        Err(err) if err.is_incomplete() => return Ok(None),
+        Err(err) => {
+            let message = format!("Tokenize error: {}", err);
+            return Err(span::format_error("<repl>", input, &message, Some(err.span())));
+        }
+    };
+    
+    match parser::parse(tokens) {
+        Ok(_) => {}
+        Err(err) if err.is_incomplete() => return Ok(None),
+        Err(err) => {
+            let message = format!("Parse error: {}", err);
+            return Err(span::format_error("<repl>", input, &message, Some(err.span())));
+        }
+    }
+    
+    // Functions defined by this input may fail long after it has been
+    // replaced by later ones, so it is kept for showing their errors. Only
+    // complete input is kept, rather than every line of it as it arrives.
+    let source = span::SourceId::register("<repl>", input);
+    let tokens = tokenizer::tokenize_source(input, source).map_err(|err| err.to_string())?;
+    parser::parse(tokens).map(Some).map_err(|err| err.to_string())
+}
+
+/// Reads the expressions given as a command's argument, which must be complete.
+fn read_argument(arg: &str) -> Result<Vec<Expr>, String> {
+    match read(arg)? {
+        Some(expressions) if !expressions.is_empty() => Ok(expressions),
+        Some(_) => Err("Expected an expression".to_string()),
+        None => Err("Incomplete expression".to_string()),
+    }
+}
+
+impl Session {
+    /// Evaluates `expr`, read from `source`, reporting any error against
+    /// `origin`. Definitions are remembered for `:save`.
+    fn eval(&mut self, expr: &Expr, source: &str, origin: &str) -> Option<Value> {
+        match evaluator::eval_expr(expr, &self.env) {
+            Ok(value) => {
+                self.record_definition(expr, source);
+                Some(value)
+            }
+            Err(err) => {
+                let message = format!("Evaluation error: {}", err);
+                eprintln!("{}", span::format_error(origin, source, &message, err.span));
+                if let Some(backtrace) = err.backtrace_report(origin, self.backtrace_depth) {
+                    eprintln!("{}", backtrace);
+                }
+                None
+            }
+        }
+    }
+    
+    /// Remembers the source of `expr` if it is a top-level `def`, `defn` or
+    /// `defmacro`, replacing any earlier definition of the same name, or a
+    /// `require` that later definitions may depend on.
+    fn record_definition(&mut self, expr: &Expr, source: &str) {
+        let ExprKind::List(elements) = &expr.kind else {
+            return;
+        };
+        
+        if let [head, ..] = &elements[..]
+            && let ExprKind::Symbol(head) = &head.kind
+            && head == "require"
+            && let Some(text) = source.get(expr.span.start..expr.span.end)
+            && !self.definitions.iter().any(|(_, recorded)| recorded == text)
+        {
+            self.definitions.push((text.to_string(), text.to_string()));
+            return;
+        }
+        
+        if let [head, name, ..] = &elements[..]
+            && let ExprKind::Symbol(head) = &head.kind
+            && matches!(head.as_str(), "def" | "defn" | "defmacro")
+            && let ExprKind::Symbol(name) = &name.kind
+            && let Some(text) = source.get(expr.span.start..expr.span.end)
+        {
+            self.definitions.retain(|(defined, _)| defined != name);
+            self.definitions.push((name.clone(), text.to_string()));
+        }
+    }
+    
+    fn run_command(&mut self, command: &Command, arg: &str) -> Flow {
+        if !command.usage.is_empty() && arg.is_empty() {
+            eprintln!("Usage: :{} {}", command.name, command.usage);
+            return Flow::Continue;
+        }
+        
+        match (command.run)(self, arg) {
+            Ok(flow) => flow,
+            Err(message) => {
+                eprintln!("{}", message);
+                Flow::Continue
+            }
+        }
+    }
+    
+    /// Candidates for completing `word`: commands if it starts with a colon,
+    /// otherwise bound names and special forms.
+    fn complete(&self, word: &str) -> Vec<String> {
+        if let Some(prefix) = word.strip_prefix(':') {
+            return COMMANDS
+                .iter()
+                .filter(|command| command.name.starts_with(prefix))
+                .map(|command| format!(":{}", command.name))
+                .collect();
+        }
+        
+        self.env
+            .names()
+            .into_iter()
+            .chain(evaluator::SPECIAL_FORMS.iter().map(|form| form.to_string()))
+            .filter(|name| name.starts_with(word))
+            .collect()
+    }
+}
+
+fn command_help(_session: &mut Session, _arg: &str) -> Result<Flow, String> {
+    println!("Commands:");
+    for command in COMMANDS {
+        let mut usage = format!(":{}", command.name);
+        if !command.usage.is_empty() {
+            usage = format!("{} {}", usage, command.usage);
+        }
+        if let Some(alias) = command.alias {
+            usage = format!("{}, :{}", usage, alias);
+        }
+        println!("  {:<18} {}", usage, command.help);
+    }
+    Ok(Flow::Continue)
+}
+
+fn command_quit(_session: &mut Session, _arg: &str) -> Result<Flow, String> {
+    println!("Goodbye!");
+    Ok(Flow::Quit)
+}
+
+fn command_env(session: &mut Session, _arg: &str) -> Result<Flow, String> {
+    for name in session.env.names() {
+        if let Some(value) = session.env.lookup(&name) {
+            println!("  {} = {}", name, value);
+        }
+    }
+    Ok(Flow::Continue)
+}
+
+fn command_doc(session: &mut Session, arg: &str) -> Result<Flow, String> {
+    if evaluator::SPECIAL_FORMS.contains(&arg) {
+        println!("{} is a special form", arg);
+        return Ok(Flow::Continue);
+    }
+    
+    let value = session.env.lookup(arg).ok_or_else(|| format!("{} is not defined", arg))?;
+    
+    let (kind, params, body) = match &value {
+        Value::Lambda { params, body, .. } => ("function", params, body),
+        Value::Macro { params, body, .. } => ("macro", params, body),
+        Value::Function(_) | Value::EnvFunction(_) => {
+            println!("{} is a built-in function", arg);
+            return Ok(Flow::Continue);
+        }
+        value => {
+            println!("{} is a {}: {}", arg, value.type_name(), value);
+            return Ok(Flow::Continue);
+        }
+    };
+    
+    // The parameter list already has parentheses; put the name inside them
+    let params = params.to_string();
+    let params = &params[1..params.len() - 1];
+    if params.is_empty() {
+        println!("({}) is a {}", arg, kind);
+    } else {
+        println!("({} {}) is a {}", arg, params, kind);
+    }
+    
+    // A string at the start of a longer body documents the definition
+    if body.len() > 1
+        && let ExprKind::String(doc) = &body[0].kind
+    {
+        println!();
+        for line in doc.lines() {
+            println!("  {}", line);
+        }
+    }
+    Ok(Flow::Continue)
+}
+
+fn command_load(session: &mut Session, arg: &str) -> Result<Flow, String> {
+    let source = fs::read_to_string(arg).map_err(|err| format!("Error reading file '{}': {}", arg, err))?;
+    
+    let tokens = tokenizer::tokenize_source(&source, span::SourceId::register(arg, &source)).map_err(|err| {
+        let message = format!("Tokenize error: {}", err);
+        span::format_error(arg, &source, &message, Some(err.span()))
+    })?;
+    
+    let expressions = parser::parse(tokens).map_err(|err| {
+        let message = format!("Parse error: {}", err);
+        span::format_error(arg, &source, &message, Some(err.span()))
+    })?;
+    
+    for expr in &expressions {
+        if session.eval(expr, &source, arg).is_none() {
+            return Ok(Flow::Continue);
+        }
+    }
+    
+    println!("Loaded {}", arg);
+    Ok(Flow::Continue)
+}
+
+fn command_save(session: &mut Session, arg: &str) -> Result<Flow, String> {
+    let mut contents = String::from(";; Definitions saved from a REPLisp session\n");
+    for (_, source) in &session.definitions {
+        contents.push('\n');
+        contents.push_str(source);
+        contents.push('\n');
+    }
+    
+    fs::write(arg, contents).map_err(|err| format!("Error writing file '{}': {}", arg, err))?;
+    println!("Saved {} definitions to {}", session.definitions.len(), arg);
+    Ok(Flow::Continue)
+}
+
+fn command_reset(session: &mut Session, _arg: &str) -> Result<Flow, String> {
+    session.env = new_environment(&session.module_path);
+    session.definitions.clear();
+    println!("Environment reset");
+    Ok(Flow::Continue)
+}
+
+fn command_time(session: &mut Session, arg: &str) -> Result<Flow, String> {
+    for expr in &read_argument(arg)? {
+        let start = Instant::now();
+        let result = session.eval(expr, arg, "<repl>");
+        let elapsed = start.elapsed();
+        
+        if let Some(value) = result {
+            println!("{}", value);
+        }
+        println!("Time: {:.3?}", elapsed);
+    }
+    Ok(Flow::Continue)
+}
+
+fn command_type(session: &mut Session, arg: &str) -> Result<Flow, String> {
+    for expr in &read_argument(arg)? {
+        if let Some(value) = session.eval(expr, arg, "<repl>") {
+            println!("{}", value.type_name());
+        }
+    }
+    Ok(Flow::Continue)
+}
+
+fn command_tokens(_session: &mut Session, arg: &str) -> Result<Flow, String> {
+    let tokens = tokenizer::tokenize(arg).map_err(|err| {
+        let message = format!("Tokenize error: {}", err);
+        span::format_error("<repl>", arg, &message, Some(err.span()))
+    })?;
+    
+    for token in &tokens {
+        println!("  {}:{}  {:?}", token.span.line, token.span.column, token.token);
+    }
+    Ok(Flow::Continue)
+}
+
+fn command_ast(_session: &mut Session, arg: &str) -> Result<Flow, String> {
+    for expr in &read_argument(arg)? {
+        print_ast(expr, 1);
+    }
+    Ok(Flow::Continue)
+}
+
+/// Prints `expr` as an indented tree, one node per line.
+fn print_ast(expr: &Expr, depth: usize) {
+    let indent = "  ".repeat(depth);
+    let location = format!("{}:{}", expr.span.line, expr.span.column);
+    
+    let (label, children): (String, Vec<&Expr>) = match &expr.kind {
+        ExprKind::Number(n) => (format!("Number {}", n), vec![]),
+        ExprKind::String(s) => (format!("String {:?}", s), vec![]),
+        ExprKind::Symbol(s) => (format!("Symbol {}", s), vec![]),
+        ExprKind::Keyword(k) => (format!("Keyword {}", k), vec![]),
+        ExprKind::Char(ch) => (format!("Char {}", tokenizer::char_literal(*ch)), vec![]),
+        ExprKind::List(elements) => ("List".to_string(), elements.iter().collect()),
+        ExprKind::Vector(elements) => ("Vector".to_string(), elements.iter().collect()),
+        ExprKind::Map(elements) => ("Map".to_string(), elements.iter().collect()),
+        ExprKind::Set(elements) => ("Set".to_string(), elements.iter().collect()),
+        ExprKind::Quote(inner) => ("Quote".to_string(), vec![inner]),
+        ExprKind::Quasiquote(inner) => ("Quasiquote".to_string(), vec![inner]),
+        ExprKind::Unquote(inner) => ("Unquote".to_string(), vec![inner]),
+        ExprKind::UnquoteSplicing(inner) => ("UnquoteSplicing".to_string(), vec![inner]),
+    };
+    
+    println!("{}{}  {}", indent, label, location);
+    for child in children {
+        print_ast(child, depth + 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn only_complete_input_is_read() {
+        assert!(matches!(read("(+ 1"), Ok(None)));
+        assert!(matches!(read("\"open"), Ok(None)));
+        assert!(read("(+ 1))").is_err());
+        
+        let expressions = read("(+ 1\n2)\n").unwrap().unwrap();
+        let source = expressions[0].span.source.unwrap().get();
+        assert_eq!((source.name.as_str(), source.text.as_str()), ("<repl>", "(+ 1\n2)\n"));
+    }
+}
@@ -0,0 +1,119 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// A region of source text. `start` and `end` are byte offsets; `line` and
+/// `column` (both 1-based, column counted in characters) locate `start`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+    /// The text the span is part of, if it was registered
+    pub source: Option<SourceId>,
+}
+
+/// Text that code was read from, such as a file or a line of REPL input.
+#[derive(Debug)]
+pub struct Source {
+    /// Where the text came from, as shown in messages
+    pub name: String,
+    pub text: String,
+}
+
+/// A handle to a registered `Source`. Spans carry one, so that an error can
+/// be shown against the text it arose in even when that isn't the text being
+/// run, as with a function defined in a module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceId(usize);
+
+thread_local! {
+    static SOURCES: RefCell<Vec<Rc<Source>>> = const { RefCell::new(Vec::new()) };
+}
+
+impl SourceId {
+    /// Registers `text`, read from `name`. Sources are kept for the rest of
+    /// the program, since code read from them may still be running.
+    pub fn register(name: &str, text: &str) -> Self {
+        SOURCES.with(|sources| {
+            let mut sources = sources.borrow_mut();
+            sources.push(Rc::new(Source {
+                name: name.to_string(),
+                text: text.to_string(),
+            }));
+            SourceId(sources.len() - 1)
+        })
+    }
+    
+    pub fn get(self) -> Rc<Source> {
+        SOURCES.with(|sources| sources.borrow()[self.0].clone())
+    }
+}
+
+impl Span {
+    /// Returns the smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            start: self.start,
+            end: other.end.max(self.start),
+            line: self.line,
+            column: self.column,
+            source: self.source,
+        }
+    }
+    
+    /// Renders the source line containing the span with a row of carets under
+    /// the spanned text. Spans covering several lines are underlined up to the
+    /// end of their first line. Returns `None` if the span doesn't fit within
+    /// `source`, as happens when it was taken from some other text.
+    pub fn excerpt(&self, source: &str) -> Option<String> {
+        if self.start > self.end
+            || self.end > source.len()
+            || !source.is_char_boundary(self.start)
+            || !source.is_char_boundary(self.end)
+        {
+            return None;
+        }
+        
+        let line_start = source[..self.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[line_start..]
+            .find('\n')
+            .map_or(source.len(), |i| line_start + i);
+        let line = &source[line_start..line_end];
+        
+        let end = self.end.min(line_end).max(self.start);
+        let width = source[self.start..end].chars().count().max(1);
+        let gutter = self.line.to_string();
+        
+        Some(format!(
+            "{} | {}\n{} | {}{}",
+            gutter,
+            line,
+            " ".repeat(gutter.len()),
+            " ".repeat(self.column.saturating_sub(1)),
+            "^".repeat(width)
+        ))
+    }
+}
+
+/// Formats an error message as `origin:line:column: message`, followed by an
+/// excerpt of the offending source when a span is known. A span from a
+/// registered source is shown against that source instead of `origin`.
+pub fn format_error(origin: &str, source: &str, message: &str, span: Option<Span>) -> String {
+    let registered = span.and_then(|span| span.source).map(SourceId::get);
+    let (origin, source) = match &registered {
+        Some(registered) => (registered.name.as_str(), registered.text.as_str()),
+        None => (origin, source),
+    };
+    
+    match span {
+        Some(span) => {
+            let location = format!("{}:{}:{}: {}", origin, span.line, span.column, message);
+            match span.excerpt(source) {
+                Some(excerpt) => format!("{}\n{}", location, excerpt),
+                None => location,
+            }
+        }
+        None => format!("{}: {}", origin, message),
+    }
+}
@@ -1,16 +1,33 @@
+use crate::bigint::BigInt;
+use crate::number::Number;
+use crate::span::{SourceId, Span};
+use std::ops::Range;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     // Literals
-    Number(f64),
+    Number(Number),
     String(String),
     Symbol(String),
+    /// A keyword's name, without its colon
+    Keyword(String),
+    Char(char),
     
     // Delimiters
     LeftParen,
     RightParen,
+    LeftBracket,
+    RightBracket,
+    LeftBrace,
+    RightBrace,
+    /// `#{`, which opens a set
+    HashBrace,
     
     // Special
     Quote,
+    Quasiquote,
+    Unquote,
+    UnquoteSplicing,
     
     // Whitespace and comments (usually ignored)
     Whitespace,
@@ -18,34 +35,80 @@ pub enum Token {
     
     // End of input
     Eof,
+}
 
-    // Unknown token
-    Unknown(String),
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenizeError {
+    UnknownCharacter(char, Span),
+    UnterminatedString(Span),
+    InvalidEscape(String, Span),
+    /// A `#\` character literal with a name that isn't recognized
+    InvalidCharacter(String, Span),
+    /// A literal that starts like a number but isn't one, why, and where.
+    MalformedNumber(String, String, Span),
+}
+
+/// A token together with the region of source it was read from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
 }
 
 pub struct Tokenizer {
     input: Vec<char>,
     position: usize,
     current_char: Option<char>,
+    byte_offset: usize,
+    line: usize,
+    column: usize,
+    token_start: Span,
+    /// The registered source being read, recorded in every span
+    source: Option<SourceId>,
 }
 
 impl Tokenizer {
     pub fn new(input: &str) -> Self {
         let chars: Vec<char> = input.chars().collect();
-        let current_char = chars.get(0).copied();
+        let current_char = chars.first().copied();
         
         Self {
             input: chars,
             position: 0,
             current_char,
+            byte_offset: 0,
+            line: 1,
+            column: 1,
+            token_start: Span::default(),
+            source: None,
         }
     }
     
     fn advance(&mut self) {
+        if let Some(ch) = self.current_char {
+            self.byte_offset += ch.len_utf8();
+            if ch == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
         self.position += 1;
         self.current_char = self.input.get(self.position).copied();
     }
     
+    /// An empty span at the current position.
+    fn mark(&self) -> Span {
+        Span {
+            start: self.byte_offset,
+            end: self.byte_offset,
+            line: self.line,
+            column: self.column,
+            source: self.source,
+        }
+    }
+    
     fn peek(&self) -> Option<char> {
         self.input.get(self.position + 1).copied()
     }
@@ -60,16 +123,13 @@ impl Tokenizer {
         }
     }
     
-    fn read_number(&mut self) -> Token {
+    fn read_number(&mut self) -> Result<Token, TokenizeError> {
         let start = self.position;
-
-        // Handle negative numbers
-        if self.current_char == Some('-') {
-            self.advance();
-        }
         
+        // Take everything up to the next delimiter, so that things like
+        // `1.2.3` or `12abc` are reported whole rather than split in two
         while let Some(ch) = self.current_char {
-            if ch.is_ascii_digit() || ch == '.' {
+            if is_symbol_char(ch) {
                 self.advance();
             } else {
                 break;
@@ -77,37 +137,189 @@ impl Tokenizer {
         }
         
         let number_str: String = self.input[start..self.position].iter().collect();
-        let number = number_str.parse::<f64>().unwrap_or(0.0);
         
-        Token::Number(number)
+        match parse_number(&number_str) {
+            Ok(number) => Ok(Token::Number(number)),
+            Err((reason, range)) => {
+                // Point at the offending part of the literal
+                let span = Span {
+                    start: self.token_start.start + range.start,
+                    end: self.token_start.start + range.end,
+                    line: self.token_start.line,
+                    column: self.token_start.column + number_str[..range.start].chars().count(),
+                    source: self.source,
+                };
+                Err(TokenizeError::MalformedNumber(number_str, reason, span))
+            }
+        }
+    }
+    
+    /// Whether a `+`, `-` or `.` at the current position begins a number
+    /// rather than a symbol, as in `-5`, `+.5` or `.5`.
+    fn starts_number(&self) -> bool {
+        let next = |i: usize| self.input.get(self.position + i).copied();
+        match (self.current_char, next(1)) {
+            (_, Some(ch)) if ch.is_ascii_digit() => true,
+            (Some('+' | '-'), Some('.')) => next(2).is_some_and(|ch| ch.is_ascii_digit()),
+            _ => false,
+        }
     }
     
-    fn read_string(&mut self) -> Token {
+    fn read_string(&mut self) -> Result<Token, TokenizeError> {
         self.advance(); // Skip opening quote
-        let start = self.position;
+        let mut string_content = String::new();
         
-        while let Some(ch) = self.current_char {
-            if ch == '"' {
-                break;
+        loop {
+            match self.current_char {
+                None => return Err(TokenizeError::UnterminatedString(self.token_start.to(self.mark()))),
+                Some('"') => {
+                    self.advance(); // Skip closing quote
+                    break;
+                }
+                Some('\\') => {
+                    let escape_start = self.mark();
+                    self.advance();
+                    match self.read_escape() {
+                        Ok(ch) => string_content.push(ch),
+                        Err(None) => return Err(TokenizeError::UnterminatedString(self.token_start.to(self.mark()))),
+                        Err(Some(message)) => {
+                            return Err(TokenizeError::InvalidEscape(message, escape_start.to(self.mark())));
+                        }
+                    }
+                }
+                Some(ch) => {
+                    string_content.push(ch);
+                    self.advance();
+                }
             }
-            // TODO: Handle escape sequences
-            self.advance();
         }
         
-        let string_content: String = self.input[start..self.position].iter().collect();
+        Ok(Token::String(string_content))
+    }
+    
+    /// Reads a character literal: `#\a`, a name like `#\space`, or a hex
+    /// code like `#\x3bb`.
+    fn read_char(&mut self) -> Result<Token, TokenizeError> {
+        self.advance(); // Skip '#'
+        self.advance(); // Skip backslash
+        
+        let Some(first) = self.current_char else {
+            return Err(TokenizeError::InvalidCharacter("#\\".to_string(), self.token_start.to(self.mark())));
+        };
+        self.advance();
         
-        if self.current_char == Some('"') {
-            self.advance(); // Skip closing quote
+        // A letter or digit may start a name; anything else stands alone
+        let mut text = String::from(first);
+        if first.is_alphanumeric() {
+            while let Some(ch) = self.current_char
+                && ch.is_alphanumeric()
+            {
+                text.push(ch);
+                self.advance();
+            }
         }
         
-        Token::String(string_content)
+        if text.chars().count() == 1 {
+            return Ok(Token::Char(first));
+        }
+        
+        char_from_name(&text)
+            .map(Token::Char)
+            .ok_or_else(|| TokenizeError::InvalidCharacter(format!("#\\{}", text), self.token_start.to(self.mark())))
+    }
+    
+    /// Reads the escape sequence following a backslash in a string literal.
+    /// Fails with a description of an invalid escape, or with `None` if the
+    /// input ends first.
+    fn read_escape(&mut self) -> Result<char, Option<String>> {
+        let ch = match self.current_char {
+            Some(ch) => ch,
+            None => return Err(None),
+        };
+        self.advance();
+        
+        match ch {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            'r' => Ok('\r'),
+            '0' => Ok('\0'),
+            '\\' => Ok('\\'),
+            '"' => Ok('"'),
+            '\'' => Ok('\''),
+            'u' => {
+                if self.current_char != Some('{') {
+                    return Err(Some("expected '{' after \\u".to_string()));
+                }
+                self.advance();
+                
+                // Stop after six digits, so an unclosed escape isn't reported
+                // along with everything up to the next `}` in the file
+                let start = self.position;
+                while let Some(ch) = self.current_char {
+                    if !ch.is_ascii_hexdigit() || self.position - start == 6 {
+                        break;
+                    }
+                    self.advance();
+                }
+                let digits: String = self.input[start..self.position].iter().collect();
+                match self.current_char {
+                    Some('}') => self.advance(),
+                    Some(_) => return Err(Some(format!("\\u{{{}", digits))),
+                    None => return Err(None),
+                }
+                
+                u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| Some(format!("\\u{{{}}}", digits)))
+            }
+            other => Err(Some(format!("\\{}", other))),
+        }
+    }
+    
+    /// Whether the `"` at the current position is followed by `hashes` `#`s.
+    fn closes_raw_string(&self, hashes: usize) -> bool {
+        (1..=hashes).all(|i| self.input.get(self.position + i) == Some(&'#'))
+    }
+    
+    /// Reads a raw string, `r"..."` or `r#"..."#`. Backslashes are taken
+    /// literally, and with one or more `#`s the string may contain `"` as long
+    /// as it isn't followed by the same number of `#`s.
+    fn read_raw_string(&mut self) -> Result<Token, TokenizeError> {
+        self.advance(); // Skip 'r'
+        
+        let mut hashes = 0;
+        while self.current_char == Some('#') {
+            hashes += 1;
+            self.advance();
+        }
+        
+        if self.current_char != Some('"') {
+            return Err(TokenizeError::UnknownCharacter('r', self.token_start));
+        }
+        self.advance();
+        
+        let start = self.position;
+        loop {
+            match self.current_char {
+                None => return Err(TokenizeError::UnterminatedString(self.token_start.to(self.mark()))),
+                Some('"') if self.closes_raw_string(hashes) => {
+                    let string_content: String = self.input[start..self.position].iter().collect();
+                    for _ in 0..=hashes {
+                        self.advance();
+                    }
+                    return Ok(Token::String(string_content));
+                }
+                Some(_) => self.advance(),
+            }
+        }
     }
     
     fn read_symbol(&mut self) -> Token {
         let start = self.position;
         
         while let Some(ch) = self.current_char {
-            if ch.is_alphanumeric() || "+-*/%=<>!?_-".contains(ch) {
+            if is_symbol_char(ch) {
                 self.advance();
             } else {
                 break;
@@ -115,9 +327,33 @@ impl Tokenizer {
         }
         
         let symbol: String = self.input[start..self.position].iter().collect();
+        
+        // Infinities and NaN are spelled like symbols
+        let unsigned = symbol.strip_prefix(['+', '-']).unwrap_or(&symbol);
+        if (unsigned == "inf" || unsigned == "nan")
+            && let Ok(number) = parse_number(&symbol)
+        {
+            return Token::Number(number);
+        }
+        
         Token::Symbol(symbol)
     }
     
+    fn read_keyword(&mut self) -> Token {
+        self.advance(); // Skip colon
+        let start = self.position;
+        
+        while let Some(ch) = self.current_char {
+            if is_symbol_char(ch) {
+                self.advance();
+            } else {
+                break;
+            }
+        }
+        
+        Token::Keyword(self.input[start..self.position].iter().collect())
+    }
+    
     fn read_comment(&mut self) -> Token {
         self.advance(); // Skip semicolon
         let start = self.position;
@@ -133,10 +369,19 @@ impl Tokenizer {
         Token::Comment(comment)
     }
     
-    pub fn next_token(&mut self) -> Token {
+    pub fn next_token(&mut self) -> Result<SpannedToken, TokenizeError> {
+        let token = self.read_token()?;
+        Ok(SpannedToken {
+            token,
+            span: self.token_start.to(self.mark()),
+        })
+    }
+    
+    fn read_token(&mut self) -> Result<Token, TokenizeError> {
         loop {
+            self.token_start = self.mark();
             match self.current_char {
-                None => return Token::Eof,
+                None => return Ok(Token::Eof),
                 
                 Some(ch) if ch.is_whitespace() => {
                     self.skip_whitespace();
@@ -145,23 +390,71 @@ impl Tokenizer {
                 
                 Some('(') => {
                     self.advance();
-                    return Token::LeftParen;
+                    return Ok(Token::LeftParen);
                 }
                 
                 Some(')') => {
                     self.advance();
-                    return Token::RightParen;
+                    return Ok(Token::RightParen);
+                }
+                
+                Some('[') => {
+                    self.advance();
+                    return Ok(Token::LeftBracket);
+                }
+                
+                Some(']') => {
+                    self.advance();
+                    return Ok(Token::RightBracket);
+                }
+                
+                Some('{') => {
+                    self.advance();
+                    return Ok(Token::LeftBrace);
+                }
+                
+                Some('}') => {
+                    self.advance();
+                    return Ok(Token::RightBrace);
+                }
+                
+                Some('#') if self.peek() == Some('\\') => {
+                    return self.read_char();
+                }
+                
+                Some('#') if self.peek() == Some('{') => {
+                    self.advance();
+                    self.advance();
+                    return Ok(Token::HashBrace);
                 }
                 
                 Some('\'') => {
                     self.advance();
-                    return Token::Quote;
+                    return Ok(Token::Quote);
+                }
+                
+                Some('`') => {
+                    self.advance();
+                    return Ok(Token::Quasiquote);
+                }
+                
+                Some(',') => {
+                    self.advance();
+                    if self.current_char == Some('@') {
+                        self.advance();
+                        return Ok(Token::UnquoteSplicing);
+                    }
+                    return Ok(Token::Unquote);
                 }
                 
                 Some('"') => {
                     return self.read_string();
                 }
                 
+                Some('r') if matches!(self.peek(), Some('"') | Some('#')) => {
+                    return self.read_raw_string();
+                }
+                
                 Some(';') => {
                     let _comment = self.read_comment();
                     // Skip comments and continue
@@ -172,38 +465,193 @@ impl Tokenizer {
                     return self.read_number();
                 }
                 
-                Some(ch) if ch == '-' && self.peek().map_or(false, |p| p.is_ascii_digit()) => {
+                Some('+' | '-' | '.') if self.starts_number() => {
                     return self.read_number();
                 }
                 
-                Some(ch) if ch.is_alphanumeric() || "+-*/%=<>!?_-".contains(ch) => {
-                    return self.read_symbol();
+                Some(ch) if is_symbol_char(ch) => {
+                    return Ok(self.read_symbol());
                 }
-
-                Some(_) => {
+                
+                Some(':') if self.peek().is_some_and(is_symbol_char) => {
+                    return Ok(self.read_keyword());
+                }
+                
+                Some(ch) => {
                     // If we reach here, it's an unknown token
-                    let unknown_char = self.current_char.unwrap();
-                    return Token::Unknown(unknown_char.to_string());
+                    return Err(TokenizeError::UnknownCharacter(ch, self.token_start.to(self.mark())));
                 }
             }
         }
     }
 }
 
-pub fn tokenize(input: &str) -> Option<Vec<Token>> {
-    let mut tokenizer = Tokenizer::new(input);
-    let mut tokens = Vec::new();
+fn is_symbol_char(ch: char) -> bool {
+    ch.is_alphanumeric() || "+-*/%=<>!?_-.&".contains(ch)
+}
+
+/// Whether `name` reads back as the same keyword when written after a colon.
+pub fn is_keyword_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(is_symbol_char)
+}
+
+/// Parses a numeric literal: an optionally signed integer, possibly with a
+/// `0x`, `0o` or `0b` radix prefix; a fraction like `3/4`; a decimal with a
+/// fractional part and/or exponent, like `1.5`, `.5` or `6.02e23`, which is
+/// read as a float; or `inf` or `nan`. Digits may be grouped with `_`, as in
+/// `1_000_000`. On failure, returns the reason and the byte range of the
+/// offending part of `text`.
+pub fn parse_number(text: &str) -> Result<Number, (String, Range<usize>)> {
+    let (negative, body) = match text.as_bytes().first() {
+        Some(b'-') => (true, &text[1..]),
+        Some(b'+') => (false, &text[1..]),
+        _ => (false, text),
+    };
+    let offset = text.len() - body.len();
+    let sign = |n: BigInt| if negative { -&n } else { n };
     
-    loop {
-        let token = tokenizer.next_token();
-        let is_eof = token == Token::Eof;
-        let is_unknown = matches!(token, Token::Unknown(_));
+    let radix = match body.get(..2) {
+        Some("0x" | "0X") => Some(16),
+        Some("0o" | "0O") => Some(8),
+        Some("0b" | "0B") => Some(2),
+        _ => None,
+    };
+    
+    if let Some(radix) = radix {
+        let digits = read_digits(text, offset + 2..text.len(), radix)?;
+        let value = digits.chars().fold(BigInt::from(0), |acc, digit| {
+            let digit = digit.to_digit(radix).unwrap_or(0) as i64;
+            &(&acc * &BigInt::from(radix as i64)) + &BigInt::from(digit)
+        });
+        return Ok(Number::from(sign(value)));
+    }
+    
+    match body {
+        "inf" => return Ok(Number::Float(if negative { f64::NEG_INFINITY } else { f64::INFINITY })),
+        "nan" => return Ok(Number::Float(f64::NAN)),
+        _ => {}
+    }
+    
+    if let Some(slash) = text.find('/') {
+        let numerator = read_digits(text, offset..slash, 10)?;
+        let denominator = read_digits(text, slash + 1..text.len(), 10)?;
+        let numerator: BigInt = numerator.parse().unwrap_or(BigInt::from(0));
+        let denominator: BigInt = denominator.parse().unwrap_or(BigInt::from(0));
+        
+        if denominator.is_zero() {
+            return Err(("zero denominator".to_string(), slash + 1..text.len()));
+        }
+        return Ok(Number::ratio(sign(numerator), denominator));
+    }
+    
+    // Split off the exponent, then the fractional part
+    let exponent_start = text.find(['e', 'E']);
+    let mantissa_end = exponent_start.unwrap_or(text.len());
+    let point = text[..mantissa_end].find('.');
+    
+    let integer_part = read_digits(text, offset..point.unwrap_or(mantissa_end), 10);
+    let fraction_part = point.map(|point| read_digits(text, point + 1..mantissa_end, 10));
+    
+    let (integer_part, fraction_part) = match (integer_part, fraction_part) {
+        (Ok(integer), None) => (integer, None),
+        (Ok(integer), Some(Ok(fraction))) => (integer, Some(fraction)),
+        // One side of the point may be empty, as in `.5` or `5.`
+        (Ok(integer), Some(Err(_))) if point == Some(mantissa_end - 1) => (integer, Some(String::new())),
+        (Err(_), Some(Ok(fraction))) if point == Some(offset) => (String::new(), Some(fraction)),
+        (Err(err), _) | (_, Some(Err(err))) => return Err(err),
+    };
+    
+    let exponent = match exponent_start {
+        Some(start) => {
+            let digits_start = match text.as_bytes().get(start + 1) {
+                Some(b'+' | b'-') => start + 2,
+                _ => start + 1,
+            };
+            let digits = read_digits(text, digits_start..text.len(), 10)
+                .map_err(|(reason, range)| (format!("{} in exponent", reason), range))?;
+            Some(format!("{}{}", &text[start + 1..digits_start], digits))
+        }
+        None => None,
+    };
+    
+    if fraction_part.is_none() && exponent.is_none() {
+        let value: BigInt = integer_part.parse().unwrap_or(BigInt::from(0));
+        return Ok(Number::from(sign(value)));
+    }
+    
+    let float = format!(
+        "{}{}.{}e{}",
+        if negative { "-" } else { "" },
+        if integer_part.is_empty() { "0" } else { &integer_part },
+        fraction_part.filter(|f| !f.is_empty()).as_deref().unwrap_or("0"),
+        exponent.as_deref().unwrap_or("0")
+    );
+    float
+        .parse()
+        .map(Number::Float)
+        .map_err(|_| ("invalid float".to_string(), 0..text.len()))
+}
 
-        if is_unknown {
-            println!("tokenize: Found unknown character: {:?}", token);
-            return None;
+/// Reads the digits of `text[range]` in the given radix, dropping any `_`
+/// separators, each of which must sit between two digits.
+fn read_digits(text: &str, range: Range<usize>, radix: u32) -> Result<String, (String, Range<usize>)> {
+    let part = &text[range.clone()];
+    if part.is_empty() {
+        return Err(("expected digits".to_string(), range.start..range.start + 1));
+    }
+    
+    let mut digits = String::new();
+    let mut previous = None;
+    let mut chars = part.char_indices().peekable();
+    
+    while let Some((i, ch)) = chars.next() {
+        let at = range.start + i..range.start + i + ch.len_utf8();
+        
+        if ch == '_' {
+            let next = chars.peek().map(|&(_, next)| next);
+            let between_digits = previous.is_some_and(|p: char| p.is_digit(radix))
+                && next.is_some_and(|n| n.is_digit(radix));
+            if !between_digits {
+                return Err(("digit separator must be between digits".to_string(), at));
+            }
+        } else if ch.is_digit(radix) {
+            digits.push(ch);
+        } else if radix == 10 {
+            return Err((format!("unexpected character {:?}", ch), at));
+        } else {
+            let base = match radix {
+                2 => "binary",
+                8 => "octal",
+                _ => "hexadecimal",
+            };
+            return Err((format!("invalid {} digit {:?}", base, ch), at));
         }
+        
+        previous = Some(ch);
+    }
+    
+    Ok(digits)
+}
 
+pub fn tokenize(input: &str) -> Result<Vec<SpannedToken>, TokenizeError> {
+    run(Tokenizer::new(input))
+}
+
+/// Tokenizes the text of a registered source, so that the spans produced
+/// refer back to it.
+pub fn tokenize_source(input: &str, source: SourceId) -> Result<Vec<SpannedToken>, TokenizeError> {
+    let mut tokenizer = Tokenizer::new(input);
+    tokenizer.source = Some(source);
+    run(tokenizer)
+}
+
+fn run(mut tokenizer: Tokenizer) -> Result<Vec<SpannedToken>, TokenizeError> {
+    let mut tokens = Vec::new();
+    
+    loop {
+        let token = tokenizer.next_token()?;
+        let is_eof = token.token == Token::Eof;
+        
         tokens.push(token);
         
         if is_eof {
@@ -211,5 +659,215 @@ pub fn tokenize(input: &str) -> Option<Vec<Token>> {
         }
     }
     
-    Some(tokens)
-}
\ No newline at end of file
+    Ok(tokens)
+}
+
+impl TokenizeError {
+    /// Whether more input could complete the source, as with an open string.
+    pub fn is_incomplete(&self) -> bool {
+        matches!(self, TokenizeError::UnterminatedString(_))
+    }
+    
+    pub fn span(&self) -> Span {
+        match self {
+            TokenizeError::UnknownCharacter(_, span) => *span,
+            TokenizeError::UnterminatedString(span) => *span,
+            TokenizeError::InvalidEscape(_, span) => *span,
+            TokenizeError::InvalidCharacter(_, span) => *span,
+            TokenizeError::MalformedNumber(_, _, span) => *span,
+        }
+    }
+}
+
+impl std::fmt::Display for TokenizeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenizeError::UnknownCharacter(ch, _) => write!(f, "Unknown character: {:?}", ch),
+            TokenizeError::UnterminatedString(_) => write!(f, "Unterminated string"),
+            TokenizeError::InvalidEscape(escape, _) => write!(f, "Invalid escape sequence: {}", escape),
+            TokenizeError::InvalidCharacter(literal, _) => write!(f, "Invalid character literal: {}", literal),
+            TokenizeError::MalformedNumber(number, reason, _) => {
+                write!(f, "Malformed number {}: {}", number, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TokenizeError {}
+
+/// Characters with names in `#\` literals, which are easier to read than the
+/// characters themselves.
+const CHAR_NAMES: &[(&str, char)] = &[
+    ("space", ' '),
+    ("newline", '\n'),
+    ("tab", '\t'),
+    ("return", '\r'),
+    ("nul", '\0'),
+];
+
+fn char_from_name(name: &str) -> Option<char> {
+    if let Some(&(_, ch)) = CHAR_NAMES.iter().find(|(n, _)| *n == name) {
+        return Some(ch);
+    }
+    
+    let hex = name.strip_prefix('x')?;
+    u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+}
+
+/// Writes a character as a literal that reads back as the same character.
+pub fn char_literal(ch: char) -> String {
+    if let Some((name, _)) = CHAR_NAMES.iter().find(|&&(_, c)| c == ch) {
+        format!("#\\{}", name)
+    } else if ch.is_control() || ch.is_whitespace() {
+        format!("#\\x{:x}", ch as u32)
+    } else {
+        format!("#\\{}", ch)
+    }
+}
+
+/// Escapes a string so that it reads back as the same string literal.
+pub fn escape_string(s: &str) -> String {
+    let mut escaped = String::new();
+    for ch in s.chars() {
+        match ch {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\0' => escaped.push_str("\\0"),
+            other => escaped.push(other),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// Parses `text` as a number and prints it back.
+    fn number(text: &str) -> String {
+        match parse_number(text) {
+            Ok(number) => number.to_string(),
+            Err((reason, _)) => panic!("{} failed to parse: {}", text, reason),
+        }
+    }
+    
+    fn malformed(text: &str) -> (String, Range<usize>) {
+        match parse_number(text) {
+            Ok(number) => panic!("{} parsed as {}", text, number),
+            Err(err) => err,
+        }
+    }
+    
+    #[test]
+    fn radix_prefixes() {
+        assert_eq!(number("0xff"), "255");
+        assert_eq!(number("0XFF"), "255");
+        assert_eq!(number("-0x10"), "-16");
+        assert_eq!(number("0o17"), "15");
+        assert_eq!(number("0b1010"), "10");
+        assert_eq!(number("0xffff_ffff_ffff_ffff"), "18446744073709551615");
+    }
+    
+    #[test]
+    fn digit_separators() {
+        assert_eq!(number("1_000_000"), "1000000");
+        assert_eq!(number("1_000.000_1"), "1000.0001");
+        assert_eq!(number("0b1111_0000"), "240");
+    }
+    
+    #[test]
+    fn decimals_and_exponents_are_floats() {
+        assert_eq!(number("1.5"), "1.5");
+        assert_eq!(number(".5"), "0.5");
+        assert_eq!(number("5."), "5.0");
+        assert_eq!(number("-.25"), "-0.25");
+        assert_eq!(number("6.02e23"), "6.02e23");
+        assert_eq!(number("1.5e3"), "1500.0");
+        assert_eq!(number("1E-2"), "0.01");
+        assert_eq!(number("2e+2"), "200.0");
+        assert_eq!(number("1e400"), "inf");
+    }
+    
+    #[test]
+    fn ratios_are_reduced() {
+        assert_eq!(number("3/4"), "3/4");
+        assert_eq!(number("6/8"), "3/4");
+        assert_eq!(number("-6/8"), "-3/4");
+        assert_eq!(number("4/2"), "2");
+    }
+    
+    #[test]
+    fn large_integers_become_bignums() {
+        assert_eq!(number("9223372036854775807"), "9223372036854775807");
+        assert_eq!(number("9223372036854775808"), "9223372036854775808");
+        assert!(matches!(parse_number("9223372036854775808"), Ok(Number::Big(_))));
+        assert!(matches!(parse_number("-9223372036854775808"), Ok(Number::Int(i64::MIN))));
+    }
+    
+    #[test]
+    fn inf_and_nan() {
+        assert_eq!(number("inf"), "inf");
+        assert_eq!(number("+inf"), "inf");
+        assert_eq!(number("-inf"), "-inf");
+        assert_eq!(number("nan"), "nan");
+    }
+    
+    #[test]
+    fn malformed_numbers_point_at_the_problem() {
+        assert_eq!(malformed("1_"), ("digit separator must be between digits".to_string(), 1..2));
+        assert_eq!(malformed("0x").0, "expected digits");
+        assert_eq!(malformed("0xZ"), ("invalid hexadecimal digit 'Z'".to_string(), 2..3));
+        assert_eq!(malformed("0b102"), ("invalid binary digit '2'".to_string(), 4..5));
+        assert_eq!(malformed("0o9").0, "invalid octal digit '9'");
+        assert_eq!(malformed("1/0"), ("zero denominator".to_string(), 2..3));
+        assert_eq!(malformed("1e").0, "expected digits in exponent");
+        assert_eq!(malformed("1e+").0, "expected digits in exponent");
+        assert_eq!(malformed("1.2.3"), ("unexpected character '.'".to_string(), 3..4));
+        assert_eq!(malformed("12abc"), ("unexpected character 'a'".to_string(), 2..3));
+        assert_eq!(malformed("1/2/3").0, "unexpected character '/'");
+    }
+    
+    #[test]
+    fn malformed_numbers_are_reported_whole() {
+        match tokenize("(+ 12abc 1)") {
+            Err(TokenizeError::MalformedNumber(text, _, span)) => {
+                assert_eq!(text, "12abc");
+                assert_eq!((span.start, span.column), (5, 6));
+            }
+            other => panic!("expected a malformed number, got {:?}", other.map(|_| ())),
+        }
+    }
+    
+    #[test]
+    fn signs_and_dots_alone_are_symbols() {
+        let tokens: Vec<Token> = tokenize("- + ... -x +5 -.5")
+            .unwrap()
+            .into_iter()
+            .map(|token| token.token)
+            .filter(|token| *token != Token::Whitespace && *token != Token::Eof)
+            .collect();
+        assert_eq!(tokens[..4], [
+            Token::Symbol("-".to_string()),
+            Token::Symbol("+".to_string()),
+            Token::Symbol("...".to_string()),
+            Token::Symbol("-x".to_string()),
+        ]);
+        assert!(matches!(&tokens[4], Token::Number(n) if n.to_string() == "5"));
+        assert!(matches!(&tokens[5], Token::Number(n) if n.to_string() == "-0.5"));
+    }
+    
+    #[test]
+    fn keyword_names_read_back_as_keywords() {
+        for name in ["born", "a-b?", "x/y", "é", "123", "<=>"] {
+            assert!(is_keyword_name(name), "{}", name);
+            let tokens = tokenize(&format!(":{}", name)).unwrap();
+            assert!(matches!(&tokens[0].token, Token::Keyword(read) if read == name), "{}", name);
+        }
+        for name in ["", "a b", "a:b", "(", "a\"b", ";x"] {
+            assert!(!is_keyword_name(name), "{:?}", name);
+        }
+    }
+}